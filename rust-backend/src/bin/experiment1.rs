@@ -1,13 +1,18 @@
 use async_graphql::{
-    http::GraphiQLSource, EmptySubscription, Object, Result, Schema, SimpleObject,
+    http::GraphiQLSource, EmptySubscription, InputObject, Object, Result, Schema, SimpleObject,
 };
 use async_std::task;
-use indicatif::ProgressBar;
 use regex::Regex;
+use rust_backend::experiments::experiment9::{
+    arithmetic_coding::{bits_per_symbol, compress, decompress},
+    evaluation::{evaluate, split_train_test, PredictNextSymbol},
+    pruning::{Pruning, PruningReport},
+    sampling::{Random, Sampling},
+};
 use serde::{Deserialize, Serialize};
 use std::io::Error;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     env,
     fmt::Debug,
     fs::File,
@@ -29,12 +34,28 @@ impl QueryRoot {
         model_input_path: String,
         text: String,
         length: usize,
+        sampling: Option<SamplingInput>,
     ) -> PredictResult {
         let model = model_from_file(&model_input_path);
         let sequence = clean_data(&text);
-        let details: Vec<Vec<PredictDetail>> = predict_next_characters(&model, &sequence, length)
+        // without sampling options the most probable character is taken
+        let mut sampler = sampling.map(|sampling| {
+            (
+                Sampling {
+                    temperature: sampling.temperature.unwrap_or(1.0) as f64,
+                    top_k: sampling.top_k,
+                    top_p: sampling.top_p.map(f64::from),
+                },
+                Random::new(sampling.seed.unwrap_or(0)),
+            )
+        });
+        let sampler = sampler
+            .as_mut()
+            .map(|(sampling, random)| (&*sampling, random));
+        let predictions = predict_next_characters(&model, &sequence, length, sampler);
+        let details: Vec<Vec<PredictDetail>> = predictions
             .iter()
-            .map(|probability_by_character| {
+            .map(|(_, probability_by_character)| {
                 let mut result: Vec<PredictDetail> = probability_by_character
                     .iter()
                     .map(|(character, probability)| PredictDetail {
@@ -48,9 +69,9 @@ impl QueryRoot {
             .collect();
         let sequence: String = [
             sequence,
-            details
+            predictions
                 .iter()
-                .map(|probabilities| probabilities.first().unwrap().character)
+                .map(|(predicted_character, _)| *predicted_character)
                 .collect(),
         ]
         .concat()
@@ -59,6 +80,46 @@ impl QueryRoot {
         let result = PredictResult { sequence, details };
         result
     }
+    async fn predict_beam(
+        &self,
+        model_input_path: String,
+        text: String,
+        length: usize,
+        beam_width: usize,
+        count: usize,
+    ) -> Vec<BeamResult> {
+        let model = model_from_file(&model_input_path);
+        let sequence = clean_data(&text);
+        predict_next_characters_beam(&model, &sequence, length, beam_width, count)
+            .into_iter()
+            .map(|(beam, score)| BeamResult {
+                sequence: beam.iter().collect(),
+                score,
+            })
+            .collect()
+    }
+    // given the split of train, the file is sliced the same way and only the held-out end is
+    // evaluated, without a test fraction all of the slice is
+    async fn evaluate(
+        &self,
+        model_input_path: String,
+        text_input_file_path: String,
+        split: Option<SplitInput>,
+        top_k: usize,
+    ) -> Result<EvaluationResult> {
+        let model = model_from_file(&model_input_path);
+        let split = split.unwrap_or_default();
+        let data = read_document(&text_input_file_path, split.slice)?;
+        let (_, test_data) = split_train_test(&data, split.test_fraction.unwrap_or(1.0));
+        let report = evaluate(&Patterns(&model), test_data, top_k);
+        Ok(EvaluationResult {
+            predictions: report.predictions,
+            top_1_accuracy: report.top_1_accuracy,
+            top_k_accuracy: report.top_k_accuracy,
+            cross_entropy: report.cross_entropy,
+            perplexity: report.perplexity,
+        })
+    }
 }
 
 struct MutationRoot;
@@ -67,105 +128,285 @@ struct MutationRoot;
 impl MutationRoot {
     async fn train(
         &self,
-        text_input_file_path: String,
-        slice: Option<usize>,
-        csv_output_file_path: Option<String>,
-        json_output_file_path: Option<String>,
-        model_output_file_path: Option<String>,
+        text_input_file_path: Option<String>,
+        text_input_file_paths: Option<Vec<String>>,
+        split: Option<SplitInput>,
+        pruning: Option<PruningInput>,
+        output: Option<OutputInput>,
     ) -> Result<Vec<PatternResult>> {
+        let split = split.unwrap_or_default();
+        let pruning = pruning.unwrap_or_default();
+        let output = output.unwrap_or_default();
+        // the single file of the original argument and the list can be given together
+        let text_input_file_paths: Vec<String> = text_input_file_path
+            .into_iter()
+            .chain(text_input_file_paths.into_iter().flatten())
+            .collect();
+        if text_input_file_paths.is_empty() {
+            return Err("textInputFilePath or textInputFilePaths is required".into());
+        }
         let data_load_now = Instant::now();
-        let string = read_file_to_string(&text_input_file_path).expect("could not read file");
-        let data = clean_data(&string[..slice.unwrap_or(string.len())].to_string());
+        // every file is a document of its own, slice and test fraction apply to each
+        let documents = text_input_file_paths
+            .iter()
+            .map(|text_input_file_path| {
+                let data = read_document(text_input_file_path, split.slice)?;
+                let (data, _) = split_train_test(&data, split.test_fraction.unwrap_or(0.0));
+                Ok(data.to_vec())
+            })
+            .collect::<Result<Vec<Vec<char>>, Error>>()?;
         let data_load_duration = data_load_now.elapsed().as_secs();
-        let data_length = data.len();
-        let pattern_creation_now = Instant::now();
-        let mut patterns: HashSet<Pattern> = HashSet::new();
-        for index in 0..data_length {
-            if let Some(current_character) = data.get(index) {
-                patterns.insert(Pattern::CurrentCharacterIs {
+        let pattern_stats_now = Instant::now();
+        let mut pattern_stats = count_patterns_in_documents(&documents);
+        let pruning_report = prune_patterns(
+            &mut pattern_stats,
+            &Pruning {
+                minimum_support: pruning.minimum_support,
+                minimum_confidence: pruning.minimum_confidence,
+                minimum_utility: pruning.minimum_utility,
+            },
+        );
+        let pattern_stats_duration = pattern_stats_now.elapsed().as_secs();
+        let report_now = Instant::now();
+        if let Some(path) = output.csv_output_file_path {
+            model_to_csv_file(&path, &pattern_stats);
+        }
+        if let Some(path) = output.json_output_file_path {
+            model_to_json_file(&path, &pattern_stats)
+        }
+        if let Some(path) = output.model_output_file_path {
+            model_to_file(&path, &pattern_stats)
+        }
+        let report_duration = report_now.elapsed().as_secs();
+        println!(
+            "load: {data_load_duration} count: {pattern_stats_duration} report: {report_duration}",
+        );
+        println!(
+            "removed by support: {} by confidence: {} by utility: {} kept: {}",
+            pruning_report.removed_by_support,
+            pruning_report.removed_by_confidence,
+            pruning_report.removed_by_utility,
+            pruning_report.kept
+        );
+        Ok(model_to_result(&pattern_stats))
+    }
+    // the cleaned text of the file, arithmetic coded with the predictions of the model
+    async fn compress(
+        &self,
+        model_input_path: String,
+        text_input_file_path: String,
+        compressed_output_file_path: String,
+    ) -> Result<CompressionResult> {
+        let model = model_from_file(&model_input_path);
+        let string = read_file_to_string(&text_input_file_path)?;
+        let data = clean_data(&string);
+        let alphabet: Vec<char> = CLEAN_ALPHABET.chars().collect();
+        let compressed = compress(&Patterns(&model), &alphabet, &data)?;
+        File::create(&compressed_output_file_path)?.write_all(&compressed)?;
+        Ok(CompressionResult {
+            characters: data.len(),
+            bytes: compressed.len(),
+            bits_per_character: bits_per_symbol(data.len(), &compressed),
+        })
+    }
+    // returns the number of characters written
+    async fn decompress(
+        &self,
+        model_input_path: String,
+        compressed_input_file_path: String,
+        text_output_file_path: String,
+    ) -> Result<usize> {
+        let model = model_from_file(&model_input_path);
+        let mut compressed = Vec::new();
+        File::open(&compressed_input_file_path)?.read_to_end(&mut compressed)?;
+        let alphabet: Vec<char> = CLEAN_ALPHABET.chars().collect();
+        let data = decompress(&Patterns(&model), &alphabet, &compressed)?;
+        File::create(&text_output_file_path)?
+            .write_all(data.iter().collect::<String>().as_bytes())?;
+        Ok(data.len())
+    }
+}
+
+// after counting: the patterns look at three characters at most, so there are never more than
+// the alphabet cubed of them to count (the experiment9 trainer skips rare rules while counting)
+fn prune_patterns(
+    pattern_stats: &mut HashMap<Pattern, PatternStats>,
+    pruning: &Pruning,
+) -> PruningReport {
+    let mut report = PruningReport::default();
+    pattern_stats.retain(|_, stats| {
+        pruning.keep(stats.condition_count, stats.consequence_count, &mut report)
+    });
+    report
+}
+
+// counted in each document on its own, so that no pattern spans two documents
+fn count_patterns_in_documents(documents: &[Vec<char>]) -> HashMap<Pattern, PatternStats> {
+    let mut pattern_stats: HashMap<Pattern, PatternStats> = HashMap::new();
+    for document in documents {
+        for (pattern, stats) in count_patterns(document) {
+            let total = pattern_stats.entry(pattern).or_default();
+            total.condition_count += stats.condition_count;
+            total.consequence_count += stats.consequence_count;
+        }
+    }
+    pattern_stats
+}
+
+// a single pass over the data, every pattern with the same condition shares its count
+fn count_patterns(data: &[char]) -> HashMap<Pattern, PatternStats> {
+    let mut pattern_stats: HashMap<Pattern, PatternStats> = HashMap::new();
+    let mut next_condition_counts: HashMap<char, u32> = HashMap::new();
+    let mut after_two_condition_counts: HashMap<(char, char), u32> = HashMap::new();
+    let mut previous_condition_counts: HashMap<char, u32> = HashMap::new();
+    for index in 0..data.len() {
+        let current_character = data[index];
+        pattern_stats
+            .entry(Pattern::CurrentCharacterIs { current_character })
+            .or_default()
+            .consequence_count += 1;
+        if let Some(next_character) = data.get(index + 1) {
+            *next_condition_counts.entry(current_character).or_default() += 1;
+            pattern_stats
+                .entry(Pattern::NextCharacterIs {
+                    current_character,
+                    next_character: *next_character,
+                })
+                .or_default()
+                .consequence_count += 1;
+            if let Some(next_next_character) = data.get(index + 2) {
+                *after_two_condition_counts
+                    .entry((current_character, *next_character))
+                    .or_default() += 1;
+                pattern_stats
+                    .entry(Pattern::NextCharacterAfterTwo {
+                        current_character_1: current_character,
+                        current_character_2: *next_character,
+                        next_character: *next_next_character,
+                    })
+                    .or_default()
+                    .consequence_count += 1;
+            }
+        }
+        if index > 0 {
+            *previous_condition_counts
+                .entry(current_character)
+                .or_default() += 1;
+            pattern_stats
+                .entry(Pattern::PreviousCharacterIs {
+                    current_character,
+                    previous_character: data[index - 1],
+                })
+                .or_default()
+                .consequence_count += 1;
+        }
+    }
+    for (pattern, stats) in pattern_stats.iter_mut() {
+        stats.condition_count = match pattern {
+            Pattern::CurrentCharacterIs { .. } => data.len() as u32,
+            Pattern::NextCharacterIs {
+                current_character, ..
+            } => next_condition_counts[current_character],
+            Pattern::PreviousCharacterIs {
+                current_character, ..
+            } => previous_condition_counts[current_character],
+            Pattern::NextCharacterAfterTwo {
+                current_character_1,
+                current_character_2,
+                ..
+            } => after_two_condition_counts[&(*current_character_1, *current_character_2)],
+        };
+    }
+    pattern_stats
+}
+
+// the original trainer: every pattern checked against the whole data
+#[cfg(test)]
+fn count_patterns_by_scanning(data: &[char]) -> HashMap<Pattern, PatternStats> {
+    use std::collections::HashSet;
+    let data_length = data.len();
+    let mut patterns: HashSet<Pattern> = HashSet::new();
+    for index in 0..data_length {
+        if let Some(current_character) = data.get(index) {
+            patterns.insert(Pattern::CurrentCharacterIs {
+                current_character: *current_character,
+            });
+            if let Some(next_character) = data.get(index + 1) {
+                patterns.insert(Pattern::NextCharacterIs {
                     current_character: *current_character,
+                    next_character: *next_character,
                 });
-                if let Some(next_character) = data.get(index + 1) {
-                    patterns.insert(Pattern::NextCharacterIs {
-                        current_character: *current_character,
-                        next_character: *next_character,
+                if let Some(next_next_character) = data.get(index + 2) {
+                    patterns.insert(Pattern::NextCharacterAfterTwo {
+                        current_character_1: *current_character,
+                        current_character_2: *next_character,
+                        next_character: *next_next_character,
                     });
-                    if let Some(next_next_character) = data.get(index + 2) {
-                        patterns.insert(Pattern::NextCharacterAfterTwo {
-                            current_character_1: *current_character,
-                            current_character_2: *next_character,
-                            next_character: *next_next_character,
-                        });
-                    }
                 }
-                if index > 0 {
-                    if let Some(previous_character) = data.get(index - 1) {
-                        patterns.insert(Pattern::PreviousCharacterIs {
-                            current_character: *current_character,
-                            previous_character: *previous_character,
-                        });
-                    }
+            }
+            if index > 0 {
+                if let Some(previous_character) = data.get(index - 1) {
+                    patterns.insert(Pattern::PreviousCharacterIs {
+                        current_character: *current_character,
+                        previous_character: *previous_character,
+                    });
                 }
             }
         }
-        let pattern_creation_duration = pattern_creation_now.elapsed().as_secs();
-        let pattern_stats_now = Instant::now();
-        let bar = ProgressBar::new(patterns.len() as u64);
-        let mut pattern_stats: HashMap<Pattern, PatternStats> = HashMap::new();
-        for pattern in &patterns {
-            bar.inc(1);
-            let stats = pattern_stats.entry(pattern.clone()).or_default();
-            for index in 0..data_length {
-                if let Some(data_current_character) = data.get(index) {
-                    if let Pattern::CurrentCharacterIs { current_character } = pattern {
-                        stats.condition_count += 1;
+    }
+    let mut pattern_stats: HashMap<Pattern, PatternStats> = HashMap::new();
+    for pattern in &patterns {
+        let stats = pattern_stats.entry(pattern.clone()).or_default();
+        for index in 0..data_length {
+            if let Some(data_current_character) = data.get(index) {
+                if let Pattern::CurrentCharacterIs { current_character } = pattern {
+                    stats.condition_count += 1;
+                    if *current_character == *data_current_character {
+                        stats.consequence_count += 1
+                    }
+                }
+                if let Some(data_next_character) = data.get(index + 1) {
+                    if let Pattern::NextCharacterIs {
+                        current_character,
+                        next_character,
+                    } = pattern
+                    {
                         if *current_character == *data_current_character {
-                            stats.consequence_count += 1
+                            stats.condition_count += 1;
+                            if *next_character == *data_next_character {
+                                stats.consequence_count += 1
+                            }
                         }
                     }
-                    if let Some(data_next_character) = data.get(index + 1) {
-                        if let Pattern::NextCharacterIs {
-                            current_character,
+                    if let Some(data_next_next_character) = data.get(index + 2) {
+                        if let Pattern::NextCharacterAfterTwo {
+                            current_character_1,
+                            current_character_2,
                             next_character,
                         } = pattern
                         {
-                            if *current_character == *data_current_character {
+                            if *current_character_1 == *data_current_character
+                                && *current_character_2 == *data_next_character
+                            {
                                 stats.condition_count += 1;
-                                if *next_character == *data_next_character {
+                                if *next_character == *data_next_next_character {
                                     stats.consequence_count += 1
                                 }
                             }
                         }
-                        if let Some(data_next_next_character) = data.get(index + 2) {
-                            if let Pattern::NextCharacterAfterTwo {
-                                current_character_1,
-                                current_character_2,
-                                next_character,
-                            } = pattern
-                            {
-                                if *current_character_1 == *data_current_character
-                                    && *current_character_2 == *data_next_character
-                                {
-                                    stats.condition_count += 1;
-                                    if *next_character == *data_next_next_character {
-                                        stats.consequence_count += 1
-                                    }
-                                }
-                            }
-                        }
                     }
-                    if index > 0 {
-                        if let Some(data_previous_character) = data.get(index - 1) {
-                            if let Pattern::PreviousCharacterIs {
-                                current_character,
-                                previous_character,
-                            } = pattern
-                            {
-                                if *current_character == *data_current_character {
-                                    stats.condition_count += 1;
-                                    if *previous_character == *data_previous_character {
-                                        stats.consequence_count += 1
-                                    }
+                }
+                if index > 0 {
+                    if let Some(data_previous_character) = data.get(index - 1) {
+                        if let Pattern::PreviousCharacterIs {
+                            current_character,
+                            previous_character,
+                        } = pattern
+                        {
+                            if *current_character == *data_current_character {
+                                stats.condition_count += 1;
+                                if *previous_character == *data_previous_character {
+                                    stats.consequence_count += 1
                                 }
                             }
                         }
@@ -173,24 +414,136 @@ impl MutationRoot {
                 }
             }
         }
-        let pattern_stats_duration = pattern_stats_now.elapsed().as_secs();
-        bar.finish();
-        let report_now = Instant::now();
-        if let Some(path) = csv_output_file_path {
-            model_to_csv_file(&path, &pattern_stats);
-        }
-        if let Some(path) = json_output_file_path {
-            model_to_json_file(&path, &pattern_stats)
-        }
-        if let Some(path) = model_output_file_path {
-            model_to_file(&path, &pattern_stats)
-        }
-        let report_duration = report_now.elapsed().as_secs();
-        println!(
-          "load: {data_load_duration} create: {pattern_creation_duration} check: {pattern_stats_duration} report: {report_duration}",
-      );
-        Ok(model_to_result(&pattern_stats))
     }
+    pattern_stats
+}
+
+#[test]
+fn single_pass_counting_equals_scanning() {
+    let text =
+        "Il piccolo principe. Quando avevo sei anni vidi, una volta, una magnifica immagine."
+            .to_string();
+    let data = clean_data(&text);
+    assert_eq!(count_patterns(&data), count_patterns_by_scanning(&data));
+}
+
+#[test]
+fn prune_rare_patterns() {
+    let data = clean_data(&"abababababac".to_string());
+    let mut pattern_stats = count_patterns(&data);
+    let patterns = pattern_stats.len();
+    let report = prune_patterns(
+        &mut pattern_stats,
+        &Pruning {
+            minimum_support: Some(2),
+            ..Default::default()
+        },
+    );
+    // the patterns conditioned on "c" (seen once) go
+    assert!(report.removed_by_support > 0);
+    assert_eq!(report.kept, pattern_stats.len());
+    assert_eq!(report.removed_by_support + report.kept, patterns);
+    assert!(pattern_stats
+        .values()
+        .all(|stats| stats.condition_count >= 2));
+}
+
+#[test]
+fn patterns_do_not_span_documents() {
+    let documents = [clean_data(&"ab".to_string()), clean_data(&"ba".to_string())];
+    let pattern_stats = count_patterns_in_documents(&documents);
+    assert!(!pattern_stats.contains_key(&Pattern::NextCharacterIs {
+        current_character: 'b',
+        next_character: 'b',
+    }));
+    let b_then_a = pattern_stats[&Pattern::NextCharacterIs {
+        current_character: 'b',
+        next_character: 'a',
+    }];
+    assert_eq!(
+        (b_then_a.condition_count, b_then_a.consequence_count),
+        (1, 1)
+    );
+}
+
+#[test]
+fn compress_round_trip() {
+    let data = clean_data(
+        &"Nel mezzo del cammin di nostra vita, mi ritrovai per una selva oscura.".to_string(),
+    );
+    let pattern_stats = count_patterns(&data);
+    let alphabet: Vec<char> = CLEAN_ALPHABET.chars().collect();
+    let compressed = compress(&Patterns(&pattern_stats), &alphabet, &data).unwrap();
+    // a map built again, as when loaded from a file, iterates in another order
+    let reloaded: HashMap<Pattern, PatternStats> = pattern_stats.into_iter().collect();
+    assert_eq!(
+        decompress(&Patterns(&reloaded), &alphabet, &compressed).unwrap(),
+        data
+    );
+    assert!(bits_per_symbol(data.len(), &compressed) < 8.0);
+}
+
+#[test]
+fn sampling_is_reproducible_from_a_seed() {
+    let data = clean_data(&"la mamma ama la lana e il lama ama la mela".to_string());
+    let model = count_patterns(&data);
+    let sequence = clean_data(&"la".to_string());
+    let sampling = Sampling {
+        temperature: 1.5,
+        top_k: None,
+        top_p: Some(0.95),
+    };
+    let sample = |seed: u64| -> String {
+        predict_next_characters(
+            &model,
+            &sequence,
+            30,
+            Some((&sampling, &mut Random::new(seed))),
+        )
+        .iter()
+        .map(|(character, _)| *character)
+        .collect()
+    };
+    assert_eq!(sample(7).chars().count(), 30);
+    assert_eq!(sample(7), sample(7));
+    assert_ne!(sample(7), sample(8));
+}
+
+#[test]
+fn prediction_stops_without_candidates() {
+    let model = HashMap::new();
+    let sequence = clean_data(&"la".to_string());
+    assert!(predict_next_characters(&model, &sequence, 5, None).is_empty());
+    let sampling = Sampling::default();
+    let sampled =
+        predict_next_characters(&model, &sequence, 5, Some((&sampling, &mut Random::new(0))));
+    assert!(sampled.is_empty());
+}
+
+#[test]
+fn predict_beam() {
+    let data = clean_data(&"la mamma ama la lana e il lama ama la mela".to_string());
+    let model = count_patterns(&data);
+    let sequence = clean_data(&"la".to_string());
+    let beams = predict_next_characters_beam(&model, &sequence, 4, 3, 2);
+    assert_eq!(beams.len(), 2);
+    assert!(beams.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    assert!(beams
+        .iter()
+        .all(|(beam, score)| beam.len() == 6 && beam.starts_with(&sequence) && *score <= 0.0));
+    // a count wider than the beam widens it
+    let beams = predict_next_characters_beam(&model, &sequence, 4, 1, 5);
+    assert_eq!(beams.len(), 5);
+    assert!(beams.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    assert!(predict_next_characters_beam(&model, &Vec::new(), 4, 3, 2).is_empty());
+}
+
+#[test]
+fn slice_by_characters() {
+    assert_eq!(slice_characters("città e paese", Some(5)), "città");
+    assert_eq!(slice_characters("città", Some(9)), "città");
+    assert_eq!(slice_characters("città", None), "città");
+    assert_eq!(slice_characters("città", Some(0)), "");
 }
 
 fn model_to_result(pattern_stats: &HashMap<Pattern, PatternStats>) -> Vec<PatternResult> {
@@ -298,24 +651,27 @@ fn model_from_file(file_path: &str) -> HashMap<Pattern, PatternStats> {
 
 fn predict_next_character(
     model: &HashMap<Pattern, PatternStats>,
-    sequence: &Vec<char>,
+    sequence: &[char],
 ) -> HashMap<char, f32> {
-    let last_character = sequence.last().unwrap();
-    let mut probability_by_character: HashMap<char, f32> = HashMap::new();
+    // patterns need a current character, an empty sequence predicts nothing
+    let last_character = match sequence.last() {
+        Some(last_character) => last_character,
+        None => return HashMap::new(),
+    };
+    // (character, kind of pattern, accuracy) summed in a fixed order below, so that a model
+    // gives the same probabilities whatever the iteration order of its map, as decompress needs
+    let mut contributions: Vec<(char, u8, f32)> = Vec::new();
     for (pattern, stats) in model {
         match pattern {
             Pattern::CurrentCharacterIs { current_character } => {
-                *probability_by_character
-                    .entry(*current_character)
-                    .or_default() += stats.accuracy();
+                contributions.push((*current_character, 0, stats.accuracy()));
             }
             Pattern::NextCharacterIs {
                 current_character,
                 next_character,
             } => {
                 if *last_character == *current_character {
-                    *probability_by_character.entry(*next_character).or_default() +=
-                        stats.accuracy()
+                    contributions.push((*next_character, 1, stats.accuracy()));
                 }
             }
             Pattern::PreviousCharacterIs {
@@ -332,37 +688,142 @@ fn predict_next_character(
                         if *last_last_character == *current_character_1
                             && *last_character == *current_character_2
                         {
-                            *probability_by_character.entry(*next_character).or_default() +=
-                                stats.accuracy()
+                            contributions.push((*next_character, 2, stats.accuracy()));
                         }
                     }
                 }
             }
         }
     }
+    contributions.sort_by_key(|(character, kind, _)| (*character, *kind));
+    let mut probability_by_character: HashMap<char, f32> = HashMap::new();
+    for (character, _, accuracy) in contributions {
+        *probability_by_character.entry(character).or_default() += accuracy;
+    }
     *probability_by_character.entry(' ').or_default() *= 0.5;
     probability_by_character
 }
 
+// the patterns seen as a model, the trait cannot be implemented on HashMap outside of its crate
+struct Patterns<'a>(&'a HashMap<Pattern, PatternStats>);
+
+impl PredictNextSymbol<char> for Patterns<'_> {
+    fn next_symbol_distribution(&self, preceding: &[char]) -> Vec<(char, f64)> {
+        // patterns need a preceding character, without one nothing is predicted
+        if preceding.is_empty() {
+            return Vec::new();
+        }
+        // patterns look at most two characters back
+        let context = preceding[preceding.len().saturating_sub(2)..].to_vec();
+        predict_next_character(self.0, &context)
+            .into_iter()
+            .map(|(character, score)| (character, score as f64))
+            .collect()
+    }
+}
+
+// picks the most probable character at each step, or samples one when a sampler is given,
+// and stops early when no pattern predicts anything
 fn predict_next_characters(
     model: &HashMap<Pattern, PatternStats>,
-    sequence: &Vec<char>,
+    sequence: &[char],
     length: usize,
-) -> Vec<HashMap<char, f32>> {
-    let mut full_sequence = sequence.clone();
+    mut sampler: Option<(&Sampling, &mut Random)>,
+) -> Vec<(char, HashMap<char, f32>)> {
+    let mut full_sequence = sequence.to_vec();
     let mut result = Vec::new();
     while result.len() < length {
         let predictions = predict_next_character(model, &full_sequence);
-        let (predicted_character, _) = predictions
+        // sorted by character so that the hash map order does not leak into the result,
+        // sampling keeps this order among equally probable characters
+        let mut distribution: Vec<(char, f64)> = predictions
             .iter()
-            .max_by(|(_, a), (_, b)| a.partial_cmp(&b).unwrap())
-            .unwrap();
-        full_sequence.push(*predicted_character);
-        result.push(predictions);
+            .map(|(character, score)| (*character, *score as f64))
+            .collect();
+        distribution.sort_by_key(|(character, _)| *character);
+        let predicted_character = match sampler.as_mut() {
+            Some((sampling, random)) => sampling.sample(&distribution, random),
+            None => Sampling {
+                temperature: 0.0,
+                ..Sampling::default()
+            }
+            .sample(&distribution, &mut Random::new(0)),
+        };
+        let predicted_character = match predicted_character {
+            Some(predicted_character) => predicted_character,
+            None => break,
+        };
+        full_sequence.push(predicted_character);
+        result.push((predicted_character, predictions));
     }
     result
 }
 
+// keeps the beam_width sequences with the highest accumulated log probability at each step,
+// and returns the best count of them; the beam is widened to count when narrower, so that
+// count sequences can be returned, and nothing is returned when nothing can be predicted
+fn predict_next_characters_beam(
+    model: &HashMap<Pattern, PatternStats>,
+    sequence: &[char],
+    length: usize,
+    beam_width: usize,
+    count: usize,
+) -> Vec<(Vec<char>, f32)> {
+    let beam_width = beam_width.max(count);
+    let mut beams: Vec<(Vec<char>, f32)> = vec![(sequence.to_vec(), 0.0)];
+    for _ in 0..length {
+        let mut candidates: Vec<(Vec<char>, f32)> = Vec::new();
+        for (beam, score) in &beams {
+            let predictions = predict_next_character(model, beam);
+            let total: f32 = predictions.values().sum();
+            for (character, probability) in predictions {
+                if probability > 0.0 {
+                    let mut candidate = beam.clone();
+                    candidate.push(character);
+                    candidates.push((candidate, score + (probability / total).ln()));
+                }
+            }
+        }
+        candidates.sort_by(|(a_beam, a), (b_beam, b)| {
+            b.partial_cmp(a).unwrap().then_with(|| a_beam.cmp(b_beam))
+        });
+        candidates.truncate(beam_width);
+        beams = candidates;
+    }
+    beams.truncate(count);
+    beams
+}
+
+// the options of the random choice of predict, the defaults of Sampling when left out
+#[derive(InputObject)]
+struct SamplingInput {
+    temperature: Option<f32>,
+    top_k: Option<usize>,
+    top_p: Option<f32>,
+    seed: Option<u64>,
+}
+
+// slice is a number of characters from the start of each file, test_fraction the end held out
+#[derive(InputObject, Default)]
+struct SplitInput {
+    slice: Option<usize>,
+    test_fraction: Option<f64>,
+}
+
+#[derive(InputObject, Default)]
+struct PruningInput {
+    minimum_support: Option<u32>,
+    minimum_confidence: Option<f64>,
+    minimum_utility: Option<f64>,
+}
+
+#[derive(InputObject, Default)]
+struct OutputInput {
+    csv_output_file_path: Option<String>,
+    json_output_file_path: Option<String>,
+    model_output_file_path: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 enum Pattern {
     CurrentCharacterIs {
@@ -383,7 +844,7 @@ enum Pattern {
     },
 }
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 struct PatternStats {
     condition_count: u32,
     consequence_count: u32,
@@ -415,6 +876,29 @@ struct PredictResult {
     details: Vec<Vec<PredictDetail>>,
 }
 
+#[derive(SimpleObject, Serialize, Deserialize)]
+struct BeamResult {
+    sequence: String,
+    score: f32,
+}
+
+#[derive(SimpleObject, Serialize, Deserialize)]
+struct CompressionResult {
+    characters: usize,
+    bytes: usize,
+    bits_per_character: f64,
+}
+
+#[derive(SimpleObject, Serialize, Deserialize)]
+struct EvaluationResult {
+    predictions: usize,
+    top_1_accuracy: f64,
+    top_k_accuracy: f64,
+    // bits per character
+    cross_entropy: f64,
+    perplexity: f64,
+}
+
 type TideResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
 fn main() -> TideResult<()> {
@@ -442,10 +926,12 @@ async fn run_graphql_server() -> TideResult<()> {
 /*
 mutation Train {
   train(
-    textInputFilePath: "il-piccolo-principe.txt"
-    csvOutputFilePath: "il-piccolo-principe.csv"
-    jsonOutputFilePath: "il-piccolo-principe.json"
-    modelOutputFilePath: "il-piccolo-principe.ron"
+    textInputFilePaths: ["il-piccolo-principe.txt"]
+    output: {
+      csvOutputFilePath: "il-piccolo-principe.csv"
+      jsonOutputFilePath: "il-piccolo-principe.json"
+      modelOutputFilePath: "il-piccolo-principe.ron"
+    }
   ) {
     pattern
     accuracy
@@ -472,8 +958,57 @@ query Predict {
     }
   }
 }
+
+query Sample {
+  predict(modelInputPath: "il-piccolo-principe.ron", text: "z", length: 12, sampling: {temperature: 0.8, topK: 5, topP: 0.9, seed: 42}) {
+    sequence
+  }
+}
+
+query PredictBeam {
+  predictBeam(modelInputPath: "il-piccolo-principe.ron", text: "z", length: 12, beamWidth: 8, count: 3) {
+    sequence
+    score
+  }
+}
+
+mutation TrainHoldingOut {
+  train(
+    textInputFilePaths: ["il-piccolo-principe.txt"]
+    split: {testFraction: 0.1}
+    pruning: {minimumSupport: 3, minimumUtility: 2}
+    output: {modelOutputFilePath: "il-piccolo-principe-train.ron"}
+  ) {
+    pattern
+  }
+}
+
+query Evaluate {
+  evaluate(modelInputPath: "il-piccolo-principe-train.ron", textInputFilePath: "il-piccolo-principe.txt", split: {testFraction: 0.1}, topK: 3) {
+    predictions
+    top1Accuracy
+    topKAccuracy
+    crossEntropy
+    perplexity
+  }
+}
+
+mutation Compress {
+  compress(modelInputPath: "il-piccolo-principe-train.ron", textInputFilePath: "il-piccolo-principe.txt", compressedOutputFilePath: "il-piccolo-principe.bin") {
+    characters
+    bytes
+    bitsPerCharacter
+  }
+}
+
+mutation Decompress {
+  decompress(modelInputPath: "il-piccolo-principe-train.ron", compressedInputFilePath: "il-piccolo-principe.bin", textOutputFilePath: "il-piccolo-principe-decompressed.txt")
+}
  */
 
+// every character clean_data keeps
+const CLEAN_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz .,";
+
 fn clean_data(string: &String) -> Vec<char> {
     let is_alpha = Regex::new("[a-zA-Z .,]").unwrap();
     let cleaned = string
@@ -484,6 +1019,20 @@ fn clean_data(string: &String) -> Vec<char> {
     cleaned
 }
 
+// the first characters of the string, at most slice of them
+fn slice_characters(string: &str, slice: Option<usize>) -> &str {
+    match slice.and_then(|slice| string.char_indices().nth(slice)) {
+        Some((end, _)) => &string[..end],
+        None => string,
+    }
+}
+
+// the cleaned characters of the file, sliced the same way by train and evaluate
+fn read_document(file_path: &str, slice: Option<usize>) -> Result<Vec<char>, Error> {
+    let string = read_file_to_string(file_path)?;
+    Ok(clean_data(&slice_characters(&string, slice).to_string()))
+}
+
 fn read_file_to_string(file_path: &str) -> Result<String, Error> {
     let mut file = File::open(file_path)?;
     let mut text = String::new();
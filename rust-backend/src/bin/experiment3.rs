@@ -1,16 +1,29 @@
 use indicatif::ProgressBar;
+use rust_backend::experiments::experiment9::{
+    evaluation::{evaluate, split_train_test, PredictNextSymbol},
+    pruning::{Pruning, PruningReport},
+};
 use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs::File;
-use std::io::{Error, Read};
+use std::io::{Error, ErrorKind, Read};
 use std::rc::Rc;
+use std::str::FromStr;
+
+const WINDOW_SIZE: usize = 3;
 
 // main function
 fn main() -> Result<(), Error> {
     let string = read_file_to_string("il-piccolo-principe.txt")?;
-    let data = string_to_character_vector_italian_lowercase_and_space(&string);
+    let all_data = string_to_character_vector_italian_lowercase_and_space(&string);
+    // with TEST_FRACTION (e.g. 0.1) the end of the text is held out to check that the
+    // statistics generalize, otherwise they are computed on all of it
+    let test_fraction: Option<f64> = env_var("TEST_FRACTION")?;
+    let (data, test_data) = split_train_test(&all_data, test_fraction.unwrap_or(0.0));
+    let data = data.to_vec();
     // dbg!(&data.iter().collect::<String>());
     let alhapbet = generate_simplified_italian_alphabet();
-    let input_terms = alphabet_to_input_terms(&alhapbet, 3);
+    let input_terms = alphabet_to_input_terms(&alhapbet, WINDOW_SIZE);
     println!("input terms: {}", input_terms.len());
     let and_terms = synthetize_and_terms(&input_terms, &data);
     println!("and terms: {}", and_terms.len());
@@ -22,7 +35,21 @@ fn main() -> Result<(), Error> {
         "term_occurrences_count_by_next_character.csv",
     )?;
     term_statistics_to_csv_file(&terms, &data, "term_statistics.csv")?;
-    let terms_statistics_relative = get_term_statistics_relative(&terms, &data);
+    // every filter is off unless set, e.g. MINIMUM_SUPPORT=3 MINIMUM_UTILITY=2
+    let pruning = Pruning {
+        minimum_support: env_var("MINIMUM_SUPPORT")?,
+        minimum_confidence: env_var("MINIMUM_CONFIDENCE")?,
+        minimum_utility: env_var("MINIMUM_UTILITY")?,
+    };
+    let (terms_statistics_relative, pruning_report) =
+        get_term_statistics_relative(&terms, &data, &pruning);
+    println!(
+        "removed by support: {} by confidence: {} by utility: {} kept: {}",
+        pruning_report.removed_by_support,
+        pruning_report.removed_by_confidence,
+        pruning_report.removed_by_utility,
+        pruning_report.kept
+    );
     // dbg!(predict_next_character_breakdown(
     //     &terms_statistics_relative,
     //     &"pri".chars().collect()
@@ -31,9 +58,34 @@ fn main() -> Result<(), Error> {
         "predicted: {}",
         predict_next_characters(&terms_statistics_relative, &"lavo".chars().collect(), 20)
     );
+    if test_fraction.is_some() {
+        let report = evaluate(&TermStatistics(&terms_statistics_relative), test_data, 3);
+        println!(
+            "held out: {} predictions, top 1 accuracy: {}, top {} accuracy: {}, cross entropy: {} bits per character, perplexity: {}",
+            report.predictions,
+            report.top_1_accuracy,
+            report.top_k,
+            report.top_k_accuracy,
+            report.cross_entropy,
+            report.perplexity
+        );
+    }
     Ok(())
 }
 
+// the value of an optional environment variable
+fn env_var<T: FromStr>(name: &str) -> Result<Option<T>, Error> {
+    match env::var(name) {
+        Ok(value) => value.parse().map(Some).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{name}: could not parse {value:?}"),
+            )
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
 // function that reads file to string, returning result
 fn read_file_to_string(path: &str) -> Result<String, Error> {
     let mut file = File::open(path)?;
@@ -84,7 +136,7 @@ enum Term {
 }
 
 impl CharacterAtWindowPosition {
-    fn check(&self, data: &Vec<char>, index: usize) -> bool {
+    fn check(&self, data: &[char], index: usize) -> bool {
         if index < self.negative_offset {
             return false;
         }
@@ -93,7 +145,7 @@ impl CharacterAtWindowPosition {
 }
 
 impl Term {
-    fn check(&self, data: &Vec<char>, index: usize) -> bool {
+    fn check(&self, data: &[char], index: usize) -> bool {
         match self {
             Term::Input(character_at_window_position) => {
                 character_at_window_position.check(data, index)
@@ -280,57 +332,95 @@ fn get_term_statistics(term: &Term, data: &Vec<char>) -> (u32, HashMap<char, u32
     (occurrences, occurrences_by_character)
 }
 
+// every term with how often it occurs and the probability of each character following it
+type TermsStatisticsRelative = HashMap<Rc<Term>, (f64, HashMap<char, f64>)>;
+
+// every (term, next character) pair is a rule for pruning, terms left without any are dropped
 fn get_term_statistics_relative(
     terms: &Vec<Rc<Term>>,
     data: &Vec<char>,
-) -> HashMap<Rc<Term>, (f64, HashMap<char, f64>)> {
-    terms
+    pruning: &Pruning,
+) -> (TermsStatisticsRelative, PruningReport) {
+    let mut report = PruningReport::default();
+    let terms_statistics_relative = terms
         .iter()
-        .map(|term| {
+        .filter_map(|term| {
             let (term_occurrences, occurrences_by_character) = get_term_statistics(term, data);
-            (
+            let by_character: HashMap<char, f64> = occurrences_by_character
+                .iter()
+                .filter(|(_, character_occurrences)| {
+                    pruning.keep(term_occurrences, **character_occurrences, &mut report)
+                })
+                .map(|(character, character_occurrences)| {
+                    (
+                        *character,
+                        *character_occurrences as f64 / term_occurrences as f64,
+                    )
+                })
+                .collect();
+            if by_character.is_empty() {
+                return None;
+            }
+            Some((
                 term.clone(),
-                (
-                    term_occurrences as f64 / data.len() as f64,
-                    occurrences_by_character
-                        .iter()
-                        .map(|(character, character_occurrences)| {
-                            (
-                                *character,
-                                *character_occurrences as f64 / term_occurrences as f64,
-                            )
-                        })
-                        .collect(),
-                ),
-            )
+                (term_occurrences as f64 / data.len() as f64, by_character),
+            ))
         })
-        .collect()
+        .collect();
+    (terms_statistics_relative, report)
 }
 
 fn predict_next_character(
-    terms_statistics_relative: &HashMap<Rc<Term>, (f64, HashMap<char, f64>)>,
-    sequence: &Vec<char>,
+    terms_statistics_relative: &TermsStatisticsRelative,
+    sequence: &[char],
 ) -> Option<char> {
-    if sequence.len() < 1 {
-        return None;
+    next_character_distribution(terms_statistics_relative, sequence)
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(character, _)| character)
+}
+
+// every character scored by its best firing term, a space counts half
+fn next_character_distribution(
+    terms_statistics_relative: &TermsStatisticsRelative,
+    sequence: &[char],
+) -> HashMap<char, f64> {
+    let mut distribution: HashMap<char, f64> = HashMap::new();
+    if sequence.is_empty() {
+        return distribution;
     }
-    terms_statistics_relative
+    for (_, (_, by_character)) in terms_statistics_relative
         .iter()
         .filter(|(term, _)| term.check(sequence, sequence.len() - 1))
-        .flat_map(|(_, (_, by_character))| by_character.iter())
-        .map(|(character, probability)| {
-            if *character == ' ' {
-                (character, probability * 0.5)
+    {
+        for (character, probability) in by_character {
+            let probability = if *character == ' ' {
+                probability * 0.5
             } else {
-                (character, *probability)
-            }
-        })
-        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-        .map(|(character, _)| *character)
+                *probability
+            };
+            let best = distribution.entry(*character).or_default();
+            *best = best.max(probability);
+        }
+    }
+    distribution
+}
+
+// the term statistics seen as a model, the trait cannot be implemented on HashMap outside of
+// its crate
+struct TermStatistics<'a>(&'a TermsStatisticsRelative);
+
+impl PredictNextSymbol<char> for TermStatistics<'_> {
+    fn next_symbol_distribution(&self, preceding: &[char]) -> Vec<(char, f64)> {
+        let context = &preceding[preceding.len().saturating_sub(WINDOW_SIZE)..];
+        next_character_distribution(self.0, context)
+            .into_iter()
+            .collect()
+    }
 }
 
 fn predict_next_character_breakdown(
-    terms_statistics_relative: &HashMap<Rc<Term>, (f64, HashMap<char, f64>)>,
+    terms_statistics_relative: &TermsStatisticsRelative,
     sequence: &Vec<char>,
 ) -> Vec<String> {
     if sequence.len() < 1 {
@@ -355,7 +445,7 @@ fn predict_next_character_breakdown(
 }
 
 fn predict_next_characters(
-    terms_statistics_relative: &HashMap<Rc<Term>, (f64, HashMap<char, f64>)>,
+    terms_statistics_relative: &TermsStatisticsRelative,
     initial_sequence: &Vec<char>,
     max_predictions: usize,
 ) -> String {
@@ -374,3 +464,27 @@ fn predict_next_characters(
 // scroll training data wth predict results
 
 // how many time a term was used to predict on trained data?
+
+#[test]
+fn evaluate_term_statistics() {
+    let data: Vec<char> = "abcabcabcabc".chars().collect();
+    let input_terms = alphabet_to_input_terms(&vec!['a', 'b', 'c'], WINDOW_SIZE);
+    let and_terms = synthetize_and_terms(&input_terms, &data);
+    let terms = [input_terms, and_terms].concat();
+    let (terms_statistics_relative, _) =
+        get_term_statistics_relative(&terms, &data, &Pruning::default());
+    let model = TermStatistics(&terms_statistics_relative);
+    let report = evaluate(&model, &data, 1);
+    assert_eq!(report.predictions, 11);
+    assert_eq!(report.top_1_accuracy, 1.0);
+    assert_eq!(
+        predict_next_characters(&terms_statistics_relative, &"ab".chars().collect(), 4),
+        "abcabc"
+    );
+    // no term fires on a character outside of the alphabet
+    assert!(model.next_symbol_distribution(&['z']).is_empty());
+    assert_eq!(
+        predict_next_character(&terms_statistics_relative, &[]),
+        None
+    );
+}
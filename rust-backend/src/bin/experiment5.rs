@@ -1,7 +1,10 @@
 mod boolean_algebra {
+    use rust_backend::experiments::experiment8::algebraic_normal_form::polynomial::{
+        self, Polynomial,
+    };
     use std::rc::Rc;
     #[derive(Debug)]
-    enum Base<Variable> {
+    pub enum Base<Variable> {
         Variable(Rc<Variable>),
         Not(Rc<Self>),
         And(Rc<Self>, Rc<Self>),
@@ -19,9 +22,10 @@ mod boolean_algebra {
         Biconditional(Rc<Self>, Rc<Self>),
     }
 
-    #[derive(Debug)]
-    enum Normal<Variable> {
+    #[derive(Debug, PartialEq)]
+    pub enum Normal<Variable> {
         Variable(Rc<Variable>),
+        False,
         True,
         And(Rc<Self>, Rc<Self>),
         Xor(Rc<Self>, Rc<Self>),
@@ -67,8 +71,35 @@ mod boolean_algebra {
     }
 
     // https://en.wikipedia.org/wiki/Algebraic_normal_form#Converting_to_algebraic_normal_form
-    // TODO
-    // impl<Variable> From<&Base<Variable>> for Normal<Variable> {}
+    // through the polynomials of the experiment8 algebraic_normal_form, a xor of conjunctions of
+    // variables, both sorted, so that equivalent terms convert to the same tree
+    impl<Variable: Ord> From<&Base<Variable>> for Normal<Variable> {
+        fn from(term: &Base<Variable>) -> Self {
+            fn polynomial<Variable: Ord>(term: &Base<Variable>) -> Polynomial<Variable> {
+                match term {
+                    Base::Variable(variable) => polynomial::variable(variable),
+                    Base::Not(x) => polynomial::not(polynomial(x)),
+                    Base::And(x, y) => polynomial::and(&polynomial(x), &polynomial(y)),
+                    Base::Or(x, y) => polynomial::or(polynomial(x), polynomial(y)),
+                }
+            }
+            type Operation<Variable> =
+                fn(Rc<Normal<Variable>>, Rc<Normal<Variable>>) -> Normal<Variable>;
+            fn right_nested<Variable>(
+                terms: impl DoubleEndedIterator<Item = Normal<Variable>>,
+                operation: Operation<Variable>,
+            ) -> Option<Normal<Variable>> {
+                terms
+                    .rev()
+                    .reduce(|rest, term| operation(Rc::new(term), Rc::new(rest)))
+            }
+            let monomials = polynomial(term).into_iter().map(|monomial| {
+                right_nested(monomial.into_iter().map(Normal::Variable), Normal::And)
+                    .unwrap_or(Normal::True)
+            });
+            right_nested(monomials, Normal::Xor).unwrap_or(Normal::False)
+        }
+    }
 
     trait Evaluate<Context> {
         fn evaluate(&self, context: &Context) -> bool;
@@ -87,4 +118,84 @@ mod boolean_algebra {
     }
 }
 
-fn main() {}
+fn main() {
+    use boolean_algebra::{Base, Normal};
+    use std::rc::Rc;
+
+    let x = Rc::new(Base::Variable(Rc::new('x')));
+    let y = Rc::new(Base::Variable(Rc::new('y')));
+    // ¬(x ∧ y) and ¬x ∨ ¬y
+    let not_and = Base::Not(Rc::new(Base::And(x.clone(), y.clone())));
+    let or_not = Base::Or(Rc::new(Base::Not(x)), Rc::new(Base::Not(y)));
+    println!("{:?}", Normal::from(&not_and));
+    println!("{:?}", Normal::from(&or_not));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::boolean_algebra::{Base, Normal};
+    use std::rc::Rc;
+
+    fn variable(name: char) -> Rc<Base<char>> {
+        Rc::new(Base::Variable(Rc::new(name)))
+    }
+
+    fn not(x: Rc<Base<char>>) -> Rc<Base<char>> {
+        Rc::new(Base::Not(x))
+    }
+
+    fn and(x: Rc<Base<char>>, y: Rc<Base<char>>) -> Rc<Base<char>> {
+        Rc::new(Base::And(x, y))
+    }
+
+    fn or(x: Rc<Base<char>>, y: Rc<Base<char>>) -> Rc<Base<char>> {
+        Rc::new(Base::Or(x, y))
+    }
+
+    fn normal(term: Rc<Base<char>>) -> Normal<char> {
+        Normal::from(&*term)
+    }
+
+    #[test]
+    fn equivalent_terms_have_the_same_normal_form() {
+        let (x, y) = (variable('x'), variable('y'));
+        // De Morgan
+        assert_eq!(
+            normal(not(and(x.clone(), y.clone()))),
+            normal(or(not(x.clone()), not(y.clone())))
+        );
+        assert_eq!(
+            normal(not(or(x.clone(), y.clone()))),
+            normal(and(not(x.clone()), not(y.clone())))
+        );
+        // double negation
+        assert_eq!(normal(not(not(x.clone()))), normal(x.clone()));
+        // commutativity
+        assert_eq!(
+            normal(or(x.clone(), y.clone())),
+            normal(or(y.clone(), x.clone()))
+        );
+        // x ∧ ¬x and x ∨ ¬x
+        assert_eq!(normal(and(x.clone(), not(x.clone()))), Normal::False);
+        assert_eq!(normal(or(x.clone(), not(x))), Normal::True);
+    }
+
+    #[test]
+    fn different_terms_have_different_normal_forms() {
+        let (x, y) = (variable('x'), variable('y'));
+        let terms = [
+            x.clone(),
+            y.clone(),
+            not(x.clone()),
+            and(x.clone(), y.clone()),
+            or(x.clone(), y.clone()),
+            and(x.clone(), not(y.clone())),
+            not(and(x, y)),
+        ];
+        for (i, a) in terms.iter().enumerate() {
+            for b in &terms[i + 1..] {
+                assert_ne!(normal(a.clone()), normal(b.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use super::boolean_algebra::*;
+use super::rc_repository::*;
+use super::utils::*;
+use std::{hash::Hash, rc::Rc};
+
+// https://en.wikipedia.org/wiki/Algebraic_normal_form
+// a xor of conjunctions of variables (Zhegalkin polynomial), both sorted, so that two terms with
+// the same truth table get the same tree and, through the repository, the same Rc
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Normal<Variable> {
+    Variable(Rc<Variable>),
+    False,
+    True,
+    And(Rc<Self>, Rc<Self>),
+    Xor(Rc<Self>, Rc<Self>),
+}
+
+// xor and and on polynomials, for any tree of ¬, ∧ and ∨ (experiment5 converts its own)
+pub mod polynomial {
+    use std::{collections::BTreeSet, rc::Rc};
+
+    // the empty monomial is True, the empty polynomial is False
+    pub type Monomial<Variable> = BTreeSet<Rc<Variable>>;
+    pub type Polynomial<Variable> = BTreeSet<Monomial<Variable>>;
+
+    pub fn variable<Variable: Ord>(variable: &Rc<Variable>) -> Polynomial<Variable> {
+        BTreeSet::from([BTreeSet::from([variable.clone()])])
+    }
+
+    pub fn constant<Variable: Ord>(value: bool) -> Polynomial<Variable> {
+        if value {
+            BTreeSet::from([BTreeSet::new()])
+        } else {
+            BTreeSet::new()
+        }
+    }
+
+    fn toggle<Variable: Ord>(polynomial: &mut Polynomial<Variable>, monomial: Monomial<Variable>) {
+        if !polynomial.remove(&monomial) {
+            polynomial.insert(monomial);
+        }
+    }
+
+    // x ⊕ x = 0, so monomials appearing in both cancel out
+    pub fn xor<Variable: Ord>(
+        x: Polynomial<Variable>,
+        y: Polynomial<Variable>,
+    ) -> Polynomial<Variable> {
+        let mut result = x;
+        for monomial in y {
+            toggle(&mut result, monomial);
+        }
+        result
+    }
+
+    // ¬x = x ⊕ 1
+    pub fn not<Variable: Ord>(x: Polynomial<Variable>) -> Polynomial<Variable> {
+        xor(x, constant(true))
+    }
+
+    // distributes the conjunction, x ∧ x = x merges the variables of each pair of monomials
+    pub fn and<Variable: Ord>(
+        x: &Polynomial<Variable>,
+        y: &Polynomial<Variable>,
+    ) -> Polynomial<Variable> {
+        let mut result = BTreeSet::new();
+        for x_monomial in x {
+            for y_monomial in y {
+                toggle(&mut result, x_monomial.union(y_monomial).cloned().collect());
+            }
+        }
+        result
+    }
+
+    // x ∨ y = x ⊕ y ⊕ xy
+    pub fn or<Variable: Ord>(
+        x: Polynomial<Variable>,
+        y: Polynomial<Variable>,
+    ) -> Polynomial<Variable> {
+        let x_and_y = and(&x, &y);
+        xor(xor(x, y), x_and_y)
+    }
+}
+
+use polynomial::{Monomial, Polynomial};
+
+// https://en.wikipedia.org/wiki/Algebraic_normal_form#Converting_to_algebraic_normal_form
+fn polynomial<Variable: Ord>(term: &Term<Variable>) -> Polynomial<Variable> {
+    match &term.0 {
+        Expr::False => polynomial::constant(false),
+        Expr::True => polynomial::constant(true),
+        Expr::Var(variable) => polynomial::variable(variable),
+        Expr::Not(x) => polynomial::not(polynomial(x)),
+        Expr::And(x, y) => polynomial::and(&polynomial(x), &polynomial(y)),
+        Expr::Or(x, y) => polynomial::or(polynomial(x), polynomial(y)),
+    }
+}
+
+impl<Variable: Ord + Hash> RcRepository<Normal<Variable>> {
+    pub fn algebraic_normal_form(&mut self, term: &Term<Variable>) -> Rc<Normal<Variable>> {
+        let monomials = polynomial(term)
+            .into_iter()
+            .map(|monomial| self.monomial(monomial))
+            .collect::<Vec<_>>();
+        monomials
+            .into_iter()
+            .rev()
+            .reduce(|rest, monomial| self.get_or_create(Normal::Xor(monomial, rest)))
+            .unwrap_or_else(|| self.get_or_create(Normal::False))
+    }
+    fn monomial(&mut self, monomial: Monomial<Variable>) -> Rc<Normal<Variable>> {
+        let variables = monomial
+            .into_iter()
+            .map(|variable| self.get_or_create(Normal::Variable(variable)))
+            .collect::<Vec<_>>();
+        variables
+            .into_iter()
+            .rev()
+            .reduce(|rest, variable| self.get_or_create(Normal::And(variable, rest)))
+            .unwrap_or_else(|| self.get_or_create(Normal::True))
+    }
+}
+
+impl<Context, Variable: EvaluateVariableIn<Context>> EvaluateIn<Context> for Normal<Variable> {
+    fn evaluate_in(&self, context: &Context) -> TruthValue {
+        use Normal::*;
+        match self {
+            Variable(variable) => variable.evaluate_variable_in(context),
+            False => TruthValue::False,
+            True => TruthValue::True,
+            And(x, y) => x.evaluate_in(context) & y.evaluate_in(context),
+            Xor(x, y) => x.evaluate_in(context) ^ y.evaluate_in(context),
+        }
+    }
+}
+
+impl<Variable: HumanReadable> HumanReadable for Normal<Variable> {
+    fn human_readable(&self) -> String {
+        use Normal::*;
+        match self {
+            Variable(variable) => variable.human_readable(),
+            False => "⊥".to_string(),
+            True => "⊤".to_string(),
+            And(x, y) => format!("({} ∧ {})", x.human_readable(), y.human_readable()),
+            Xor(x, y) => format!("({} ⊕ {})", x.human_readable(), y.human_readable()),
+        }
+    }
+}
+
+#[test]
+fn test_algebraic_normal_form_is_canonical() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let mut normal_repository: RcRepository<Normal<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let not_a = term_repository.not(&a);
+    let not_b = term_repository.not(&b);
+    let a_or_b = term_repository.or(&a, &b);
+    let not_a_and_not_b = term_repository.and(&not_a, &not_b);
+    let de_morgan_a_or_b = term_repository.not(&not_a_and_not_b);
+    let b_or_a = term_repository.or(&b, &a);
+    assert!(Rc::ptr_eq(
+        &normal_repository.algebraic_normal_form(&a_or_b),
+        &normal_repository.algebraic_normal_form(&de_morgan_a_or_b)
+    ));
+    assert!(Rc::ptr_eq(
+        &normal_repository.algebraic_normal_form(&a_or_b),
+        &normal_repository.algebraic_normal_form(&b_or_a)
+    ));
+    let a_and_not_a = term_repository.and(&a, &not_a);
+    assert_eq!(
+        *normal_repository.algebraic_normal_form(&a_and_not_a),
+        Normal::False
+    );
+    let a_or_not_a = term_repository.or(&a, &not_a);
+    assert_eq!(
+        *normal_repository.algebraic_normal_form(&a_or_not_a),
+        Normal::True
+    );
+    let not_not_a = term_repository.not(&not_a);
+    assert!(Rc::ptr_eq(
+        &normal_repository.algebraic_normal_form(&a),
+        &normal_repository.algebraic_normal_form(&not_not_a)
+    ));
+    let a_and_b = term_repository.and(&a, &b);
+    assert_ne!(
+        normal_repository.algebraic_normal_form(&a_and_b),
+        normal_repository.algebraic_normal_form(&a_or_b)
+    );
+    let a_iff_b = term_repository.biconditional(&a, &b);
+    assert_eq!(
+        normal_repository
+            .algebraic_normal_form(&a_iff_b)
+            .human_readable(),
+        "(⊤ ⊕ (a ⊕ b))"
+    );
+    assert_eq!(
+        normal_repository
+            .algebraic_normal_form(&a_or_b)
+            .human_readable(),
+        "(a ⊕ ((a ∧ b) ⊕ b))"
+    );
+}
+
+#[test]
+fn test_algebraic_normal_form_evaluates_like_the_term() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let mut normal_repository: RcRepository<Normal<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let c = term_repository.var(&variable_repository.get_or_create('c'));
+    let a_then_b = term_repository.conditional(&a, &b);
+    let not_c = term_repository.not(&c);
+    let b_iff_not_c = term_repository.biconditional(&b, &not_c);
+    let term = term_repository.or(&a_then_b, &b_iff_not_c);
+    let term = term_repository.and(&term, &c);
+    let normal = normal_repository.algebraic_normal_form(&term);
+    for combination in 0..8 {
+        let context = std::collections::HashMap::from([
+            ('a', combination & 1 == 1),
+            ('b', combination & 2 == 2),
+            ('c', combination & 4 == 4),
+        ]);
+        assert_eq!(normal.evaluate_in(&context), term.evaluate_in(&context));
+    }
+}
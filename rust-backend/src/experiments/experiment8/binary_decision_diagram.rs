@@ -0,0 +1,597 @@
+use super::boolean_algebra::*;
+use super::rc_repository::*;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+// https://en.wikipedia.org/wiki/Binary_decision_diagram
+// a reduced ordered binary decision diagram, nodes are unique within their repository so two
+// diagrams from the same repository are equivalent exactly when they are the same node, which is
+// what equality and hashing compare (in constant time, unlike TruthTable)
+#[derive(Debug)]
+pub struct Bdd<Variable>(Rc<Node<Variable>>);
+
+impl<Variable> Clone for Bdd<Variable> {
+    fn clone(&self) -> Self {
+        Bdd(self.0.clone())
+    }
+}
+
+impl<Variable> PartialEq for Bdd<Variable> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<Variable> Eq for Bdd<Variable> {}
+
+impl<Variable> Hash for Bdd<Variable> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Node<Variable> {
+    False,
+    True,
+    // level is the position of the variable in the order, low is followed when it is false
+    Decision {
+        level: usize,
+        variable: Rc<Variable>,
+        low: Bdd<Variable>,
+        high: Bdd<Variable>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operation {
+    And,
+    Or,
+    Xor,
+}
+
+// the size of a diagram depends a lot on the order of its variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableOrdering {
+    Sorted,
+    // depth first, left to right, keeps variables of the same subterm close to each other
+    FirstAppearance,
+    MostFrequentFirst,
+}
+
+pub fn variable_order<'a, Variable: Ord + Hash + 'a>(
+    terms: impl IntoIterator<Item = &'a Term<Variable>>,
+    ordering: VariableOrdering,
+) -> Vec<Rc<Variable>> {
+    fn visit<Variable: Eq + Hash>(
+        term: &Term<Variable>,
+        order: &mut Vec<Rc<Variable>>,
+        occurrences: &mut HashMap<Rc<Variable>, usize>,
+    ) {
+        match &term.0 {
+            Expr::False | Expr::True => {}
+            Expr::Var(variable) => {
+                let count = occurrences.entry(variable.clone()).or_insert(0);
+                if *count == 0 {
+                    order.push(variable.clone());
+                }
+                *count += 1;
+            }
+            Expr::Not(x) => visit(x, order, occurrences),
+            Expr::And(x, y) | Expr::Or(x, y) => {
+                visit(x, order, occurrences);
+                visit(y, order, occurrences);
+            }
+        }
+    }
+    let mut order = Vec::new();
+    let mut occurrences = HashMap::new();
+    for term in terms {
+        visit(term, &mut order, &mut occurrences);
+    }
+    match ordering {
+        VariableOrdering::Sorted => order.sort(),
+        VariableOrdering::FirstAppearance => {}
+        // stable, so ties keep their first appearance order
+        VariableOrdering::MostFrequentFirst => {
+            order.sort_by_key(|variable| std::cmp::Reverse(occurrences[variable]))
+        }
+    }
+    order
+}
+
+pub struct BddRepository<Variable> {
+    nodes: RcRepository<Node<Variable>>,
+    order: Vec<Rc<Variable>>,
+    levels: HashMap<Rc<Variable>, usize>,
+    not_cache: HashMap<Bdd<Variable>, Bdd<Variable>>,
+    apply_cache: HashMap<(Operation, Bdd<Variable>, Bdd<Variable>), Bdd<Variable>>,
+}
+
+impl<Variable: Eq + Hash> BddRepository<Variable> {
+    // variables missing from the order are appended when first used
+    pub fn new(order: Vec<Rc<Variable>>) -> Self {
+        let levels = order
+            .iter()
+            .enumerate()
+            .map(|(level, variable)| (variable.clone(), level))
+            .collect();
+        BddRepository {
+            nodes: RcRepository::new(),
+            order,
+            levels,
+            not_cache: HashMap::new(),
+            apply_cache: HashMap::new(),
+        }
+    }
+
+    pub fn order(&self) -> &[Rc<Variable>] {
+        &self.order
+    }
+
+    pub fn constant(&mut self, value: bool) -> Bdd<Variable> {
+        Bdd(self
+            .nodes
+            .get_or_create(if value { Node::True } else { Node::False }))
+    }
+
+    pub fn var(&mut self, variable: &Rc<Variable>) -> Bdd<Variable> {
+        let level = match self.levels.get(variable) {
+            Some(level) => *level,
+            None => {
+                self.order.push(variable.clone());
+                self.levels.insert(variable.clone(), self.order.len() - 1);
+                self.order.len() - 1
+            }
+        };
+        let low = self.constant(false);
+        let high = self.constant(true);
+        self.decision(level, variable, low, high)
+    }
+
+    // the reduction rule, a decision between two equal diagrams is not a decision
+    fn decision(
+        &mut self,
+        level: usize,
+        variable: &Rc<Variable>,
+        low: Bdd<Variable>,
+        high: Bdd<Variable>,
+    ) -> Bdd<Variable> {
+        if low == high {
+            return low;
+        }
+        Bdd(self.nodes.get_or_create(Node::Decision {
+            level,
+            variable: variable.clone(),
+            low,
+            high,
+        }))
+    }
+
+    pub fn not(&mut self, x: &Bdd<Variable>) -> Bdd<Variable> {
+        if let Some(result) = self.not_cache.get(x) {
+            return result.clone();
+        }
+        let result = match x.0.as_ref() {
+            Node::False => self.constant(true),
+            Node::True => self.constant(false),
+            Node::Decision {
+                level,
+                variable,
+                low,
+                high,
+            } => {
+                let low = self.not(low);
+                let high = self.not(high);
+                self.decision(*level, variable, low, high)
+            }
+        };
+        self.not_cache.insert(x.clone(), result.clone());
+        result
+    }
+
+    pub fn and(&mut self, x: &Bdd<Variable>, y: &Bdd<Variable>) -> Bdd<Variable> {
+        self.apply(Operation::And, x, y)
+    }
+
+    pub fn or(&mut self, x: &Bdd<Variable>, y: &Bdd<Variable>) -> Bdd<Variable> {
+        self.apply(Operation::Or, x, y)
+    }
+
+    pub fn xor(&mut self, x: &Bdd<Variable>, y: &Bdd<Variable>) -> Bdd<Variable> {
+        self.apply(Operation::Xor, x, y)
+    }
+
+    pub fn is_equivalent(&self, x: &Bdd<Variable>, y: &Bdd<Variable>) -> bool {
+        x == y
+    }
+
+    fn apply(
+        &mut self,
+        operation: Operation,
+        x: &Bdd<Variable>,
+        y: &Bdd<Variable>,
+    ) -> Bdd<Variable> {
+        use Node::*;
+        match (operation, x.0.as_ref(), y.0.as_ref()) {
+            (Operation::And, False, _) | (Operation::And, _, False) => return self.constant(false),
+            (Operation::And, True, _) | (Operation::Or, False, _) | (Operation::Xor, False, _) => {
+                return y.clone()
+            }
+            (Operation::And, _, True) | (Operation::Or, _, False) | (Operation::Xor, _, False) => {
+                return x.clone()
+            }
+            (Operation::Or, True, _) | (Operation::Or, _, True) => return self.constant(true),
+            (Operation::Xor, True, _) => return self.not(y),
+            (Operation::Xor, _, True) => return self.not(x),
+            (Operation::And, _, _) | (Operation::Or, _, _) if x == y => return x.clone(),
+            (Operation::Xor, _, _) if x == y => return self.constant(false),
+            _ => {}
+        }
+        let key = (operation, x.clone(), y.clone());
+        if let Some(result) = self.apply_cache.get(&key) {
+            return result.clone();
+        }
+        // split on the topmost variable of the two
+        let (level, variable) = match (x.0.as_ref(), y.0.as_ref()) {
+            (
+                Decision {
+                    level: x_level,
+                    variable: x_variable,
+                    ..
+                },
+                Decision {
+                    level: y_level,
+                    variable: y_variable,
+                    ..
+                },
+            ) => {
+                if x_level <= y_level {
+                    (*x_level, x_variable.clone())
+                } else {
+                    (*y_level, y_variable.clone())
+                }
+            }
+            (
+                Decision {
+                    level, variable, ..
+                },
+                _,
+            )
+            | (
+                _,
+                Decision {
+                    level, variable, ..
+                },
+            ) => (*level, variable.clone()),
+            _ => unreachable!("constants are handled above"),
+        };
+        let (x_low, x_high) = cofactors(x, level);
+        let (y_low, y_high) = cofactors(y, level);
+        let low = self.apply(operation, &x_low, &y_low);
+        let high = self.apply(operation, &x_high, &y_high);
+        let result = self.decision(level, &variable, low, high);
+        self.apply_cache.insert(key, result.clone());
+        result
+    }
+
+    pub fn of_term(&mut self, term: &Term<Variable>) -> Bdd<Variable> {
+        match &term.0 {
+            Expr::False => self.constant(false),
+            Expr::True => self.constant(true),
+            Expr::Var(variable) => self.var(variable),
+            Expr::Not(x) => {
+                let x = self.of_term(x);
+                self.not(&x)
+            }
+            Expr::And(x, y) => {
+                let x = self.of_term(x);
+                let y = self.of_term(y);
+                self.and(&x, &y)
+            }
+            Expr::Or(x, y) => {
+                let x = self.of_term(x);
+                let y = self.of_term(y);
+                self.or(&x, &y)
+            }
+        }
+    }
+
+    // over every variable of the repository, not only the ones the diagram depends on,
+    // None when the count does not fit in a u128 (with 128 variables or more)
+    pub fn satisfying_assignment_count(&self, x: &Bdd<Variable>) -> Option<u128> {
+        fn count<Variable>(
+            x: &Bdd<Variable>,
+            variable_count: usize,
+            counts: &mut HashMap<Bdd<Variable>, Option<u128>>,
+        ) -> Option<u128> {
+            // assignments of the variables from the level of x down
+            if let Some(result) = counts.get(x) {
+                return *result;
+            }
+            let result = match x.0.as_ref() {
+                Node::False => Some(0),
+                Node::True => Some(1),
+                Node::Decision {
+                    level, low, high, ..
+                } => {
+                    let [low, high] = [low, high].map(|branch| {
+                        let skipped = level_of(branch, variable_count) - level - 1;
+                        times_power_of_two(count(branch, variable_count, counts)?, skipped)
+                    });
+                    low?.checked_add(high?)
+                }
+            };
+            counts.insert(x.clone(), result);
+            result
+        }
+        let variable_count = self.order.len();
+        times_power_of_two(
+            count(x, variable_count, &mut HashMap::new())?,
+            level_of(x, variable_count),
+        )
+    }
+
+    pub fn node_count(&self, x: &Bdd<Variable>) -> usize {
+        fn visit<Variable>(x: &Bdd<Variable>, visited: &mut HashSet<Bdd<Variable>>) {
+            if visited.insert(x.clone()) {
+                if let Node::Decision { low, high, .. } = x.0.as_ref() {
+                    visit(low, visited);
+                    visit(high, visited);
+                }
+            }
+        }
+        let mut visited = HashSet::new();
+        visit(x, &mut visited);
+        visited.len()
+    }
+}
+
+fn level_of<Variable>(x: &Bdd<Variable>, variable_count: usize) -> usize {
+    match x.0.as_ref() {
+        Node::Decision { level, .. } => *level,
+        Node::False | Node::True => variable_count,
+    }
+}
+
+// x with the variable at level fixed to false and to true
+fn cofactors<Variable>(x: &Bdd<Variable>, level: usize) -> (Bdd<Variable>, Bdd<Variable>) {
+    match x.0.as_ref() {
+        Node::Decision {
+            level: x_level,
+            low,
+            high,
+            ..
+        } if *x_level == level => (low.clone(), high.clone()),
+        _ => (x.clone(), x.clone()),
+    }
+}
+
+// like TruthTable, with unknown variables the result is known only when both branches agree
+impl<Context, Variable: EvaluateVariableIn<Context>> EvaluateIn<Context> for Bdd<Variable> {
+    fn evaluate_in(&self, context: &Context) -> TruthValue {
+        match self.0.as_ref() {
+            Node::False => TruthValue::False,
+            Node::True => TruthValue::True,
+            Node::Decision {
+                variable,
+                low,
+                high,
+                ..
+            } => match variable.evaluate_variable_in(context) {
+                TruthValue::False => low.evaluate_in(context),
+                TruthValue::True => high.evaluate_in(context),
+                TruthValue::Unknown => {
+                    let low = low.evaluate_in(context);
+                    if low == TruthValue::Unknown || low != high.evaluate_in(context) {
+                        TruthValue::Unknown
+                    } else {
+                        low
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[test]
+fn test_equivalent_terms_share_a_diagram() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let mut bdd_repository: BddRepository<char> = BddRepository::new(Vec::new());
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let not_a = term_repository.not(&a);
+    let not_b = term_repository.not(&b);
+    let a_or_b = term_repository.or(&a, &b);
+    let not_a_and_not_b = term_repository.and(&not_a, &not_b);
+    let de_morgan_a_or_b = term_repository.not(&not_a_and_not_b);
+    let x = bdd_repository.of_term(&a_or_b);
+    let y = bdd_repository.of_term(&de_morgan_a_or_b);
+    assert!(bdd_repository.is_equivalent(&x, &y));
+    let a_iff_b = term_repository.biconditional(&a, &b);
+    let a_then_b = term_repository.conditional(&a, &b);
+    let b_then_a = term_repository.conditional(&b, &a);
+    let both_ways = term_repository.and(&a_then_b, &b_then_a);
+    let x = bdd_repository.of_term(&a_iff_b);
+    let y = bdd_repository.of_term(&both_ways);
+    assert_eq!(x, y);
+    let a_bdd = bdd_repository.of_term(&a);
+    let b_bdd = bdd_repository.of_term(&b);
+    let a_xor_b = bdd_repository.xor(&a_bdd, &b_bdd);
+    assert_eq!(bdd_repository.not(&a_xor_b), x);
+    let a_and_not_a = term_repository.and(&a, &not_a);
+    assert_eq!(
+        bdd_repository.of_term(&a_and_not_a),
+        bdd_repository.constant(false)
+    );
+    // a drop-in dedup key, like the truth tables in experiment7
+    let a_and_a = term_repository.and(&a, &a);
+    let a_or_a = term_repository.or(&a, &a);
+    let not_not_a = term_repository.not(&not_a);
+    let keys: HashSet<Bdd<char>> = [&a, &a_and_a, &a_or_a, &not_not_a, &b]
+        .into_iter()
+        .map(|term| bdd_repository.of_term(term))
+        .collect();
+    assert_eq!(keys.len(), 2);
+}
+
+// count * 2^exponent, None on overflow
+fn times_power_of_two(count: u128, exponent: usize) -> Option<u128> {
+    if count == 0 {
+        return Some(0);
+    }
+    count.checked_mul(1u128.checked_shl(exponent.try_into().ok()?)?)
+}
+
+#[test]
+fn test_satisfying_assignment_count() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let c = variable_repository.get_or_create('c');
+    let mut bdd_repository: BddRepository<char> = BddRepository::new(vec![c]);
+    let a_or_b = term_repository.or(&a, &b);
+    let x = bdd_repository.of_term(&a_or_b);
+    assert_eq!(bdd_repository.satisfying_assignment_count(&x), Some(6));
+    let a_and_b = term_repository.and(&a, &b);
+    let x = bdd_repository.of_term(&a_and_b);
+    assert_eq!(bdd_repository.satisfying_assignment_count(&x), Some(2));
+    let x = bdd_repository.constant(true);
+    assert_eq!(bdd_repository.satisfying_assignment_count(&x), Some(8));
+}
+
+#[test]
+fn test_satisfying_assignment_count_overflow() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let order: Vec<_> = (0..128)
+        .map(|index| variable_repository.get_or_create(char::from_u32(0x100 + index).unwrap()))
+        .collect();
+    let mut bdd_repository: BddRepository<char> = BddRepository::new(order.clone());
+    let first = bdd_repository.var(&order[0]);
+    let last = bdd_repository.var(&order[127]);
+    assert_eq!(
+        bdd_repository.satisfying_assignment_count(&first),
+        Some(1 << 127)
+    );
+    assert_eq!(
+        bdd_repository.satisfying_assignment_count(&last),
+        Some(1 << 127)
+    );
+    let first_or_last = bdd_repository.or(&first, &last);
+    assert_eq!(
+        bdd_repository.satisfying_assignment_count(&first_or_last),
+        Some(3 << 126)
+    );
+    // 2^128
+    let x = bdd_repository.constant(true);
+    assert_eq!(bdd_repository.satisfying_assignment_count(&x), None);
+    let x = bdd_repository.constant(false);
+    assert_eq!(bdd_repository.satisfying_assignment_count(&x), Some(0));
+}
+
+#[test]
+fn test_more_variables_than_a_truth_table_can_hold() {
+    use super::character_window::*;
+    let mut variable_repository: RcRepository<CharacterInWindow> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<CharacterInWindow>> = RcRepository::new();
+    let mut bdd_repository: BddRepository<CharacterInWindow> = BddRepository::new(Vec::new());
+    // exactly one character at each offset
+    let mut window = bdd_repository.constant(true);
+    for negative_offset in 0..3 {
+        let characters: Vec<_> = ('a'..='z')
+            .map(|character| {
+                let variable = variable_repository.character_in_window(negative_offset, character);
+                bdd_repository.of_term(&term_repository.var(&variable))
+            })
+            .collect();
+        let mut none = bdd_repository.constant(true);
+        let mut one = bdd_repository.constant(false);
+        for character in characters {
+            let not_character = bdd_repository.not(&character);
+            let one_and_not_character = bdd_repository.and(&one, &not_character);
+            let none_and_character = bdd_repository.and(&none, &character);
+            one = bdd_repository.or(&one_and_not_character, &none_and_character);
+            none = bdd_repository.and(&none, &not_character);
+        }
+        window = bdd_repository.and(&window, &one);
+    }
+    assert_eq!(bdd_repository.order().len(), 78);
+    assert_eq!(
+        bdd_repository.satisfying_assignment_count(&window),
+        Some(26 * 26 * 26)
+    );
+}
+
+#[test]
+fn test_variable_ordering() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let pairs: Vec<_> = [('a', 'x'), ('b', 'y'), ('c', 'z')]
+        .into_iter()
+        .map(|(left, right)| {
+            let left = term_repository.var(&variable_repository.get_or_create(left));
+            let right = term_repository.var(&variable_repository.get_or_create(right));
+            term_repository.and(&left, &right)
+        })
+        .collect();
+    let term = pairs
+        .into_iter()
+        .reduce(|sum, pair| term_repository.or(&sum, &pair))
+        .unwrap();
+    let sorted_order = variable_order([term.as_ref()], VariableOrdering::Sorted);
+    let first_appearance_order = variable_order([term.as_ref()], VariableOrdering::FirstAppearance);
+    assert_eq!(
+        first_appearance_order
+            .iter()
+            .map(|variable| **variable)
+            .collect::<String>(),
+        "axbycz"
+    );
+    let mut sorted = BddRepository::new(sorted_order);
+    let mut first_appearance = BddRepository::new(first_appearance_order);
+    let sorted_bdd = sorted.of_term(&term);
+    let first_appearance_bdd = first_appearance.of_term(&term);
+    assert_eq!(first_appearance.node_count(&first_appearance_bdd), 8);
+    assert!(sorted.node_count(&sorted_bdd) > first_appearance.node_count(&first_appearance_bdd));
+    assert_eq!(
+        sorted.satisfying_assignment_count(&sorted_bdd),
+        first_appearance.satisfying_assignment_count(&first_appearance_bdd)
+    );
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let b_and_a = term_repository.and(&b, &a);
+    let most_frequent_first_order = variable_order(
+        [term.as_ref(), b_and_a.as_ref()],
+        VariableOrdering::MostFrequentFirst,
+    );
+    assert_eq!(*most_frequent_first_order[0], 'a');
+    assert_eq!(*most_frequent_first_order[1], 'b');
+}
+
+#[test]
+fn test_evaluate_with_unknown_variables() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let mut bdd_repository: BddRepository<char> = BddRepository::new(Vec::new());
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let a_and_b = term_repository.and(&a, &b);
+    let not_a = term_repository.not(&a);
+    let a_or_not_a = term_repository.or(&a, &not_a);
+    let a_and_b = bdd_repository.of_term(&a_and_b);
+    let a_or_not_a = bdd_repository.of_term(&a_or_not_a);
+    let only_b_false = HashMap::from([('b', false)]);
+    let only_b_true = HashMap::from([('b', true)]);
+    assert_eq!(a_and_b.evaluate_in(&only_b_false), false);
+    assert_eq!(a_and_b.evaluate_in(&only_b_true), TruthValue::Unknown);
+    assert_eq!(a_or_not_a.evaluate_in(&only_b_false), true);
+    assert_eq!(
+        a_and_b.evaluate_in(&HashMap::from([('a', true), ('b', true)])),
+        true
+    );
+}
@@ -1,21 +1,28 @@
 use super::rc_repository::*;
 use super::utils::*;
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
     hash::Hash,
+    ops::{BitAnd, BitOr, BitXor, Not},
     rc::Rc,
 };
 
+// Kleene's strong three-valued logic, Unknown when a variable cannot be evaluated in a context
+// (like a window position before the start of the data)
+pub use crate::experiments::experiment9::boolean_algebra::TruthValue;
+
 pub trait EvaluateVariableIn<Context> {
-    fn evaluate_variable_in(&self, context: &Context) -> bool;
+    fn evaluate_variable_in(&self, context: &Context) -> TruthValue;
 }
 
 pub trait EvaluateIn<Context> {
-    fn evaluate_in(&self, context: &Context) -> bool;
+    fn evaluate_in(&self, context: &Context) -> TruthValue;
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
-enum Expr<Variable> {
+pub(super) enum Expr<Variable> {
+    False,
+    True,
     Var(Rc<Variable>),
     Not(Rc<Term<Variable>>),
     And(Rc<Term<Variable>>, Rc<Term<Variable>>),
@@ -23,9 +30,12 @@ enum Expr<Variable> {
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub struct Term<Variable>(Expr<Variable>);
+pub struct Term<Variable>(pub(super) Expr<Variable>);
 
 impl<Variable: Eq + Hash> RcRepository<Term<Variable>> {
+    pub fn constant(&mut self, value: bool) -> Rc<Term<Variable>> {
+        self.get_or_create(Term(if value { Expr::True } else { Expr::False }))
+    }
     pub fn var(&mut self, x: &Rc<Variable>) -> Rc<Term<Variable>> {
         self.get_or_create(Term(Expr::Var(x.clone())))
     }
@@ -60,13 +70,15 @@ impl<Variable: Eq + Hash> RcRepository<Term<Variable>> {
 }
 
 impl<Context, Variable: EvaluateVariableIn<Context>> EvaluateIn<Context> for Term<Variable> {
-    fn evaluate_in(&self, context: &Context) -> bool {
+    fn evaluate_in(&self, context: &Context) -> TruthValue {
         use Expr::*;
         match &self.0 {
+            False => TruthValue::False,
+            True => TruthValue::True,
             Var(variable) => variable.evaluate_variable_in(context),
             Not(x) => !x.evaluate_in(context),
-            And(x, y) => x.evaluate_in(context) && y.evaluate_in(context),
-            Or(x, y) => x.evaluate_in(context) || y.evaluate_in(context),
+            And(x, y) => x.evaluate_in(context) & y.evaluate_in(context),
+            Or(x, y) => x.evaluate_in(context) | y.evaluate_in(context),
         }
     }
 }
@@ -75,6 +87,8 @@ impl<Variable: HumanReadable> HumanReadable for Term<Variable> {
     fn human_readable(&self) -> String {
         use Expr::*;
         match &self.0 {
+            False => "⊥".to_string(),
+            True => "⊤".to_string(),
             Var(variable) => variable.human_readable(),
             Not(x) => format!("¬{}", x.human_readable()),
             And(x, y) => format!("({} ∧ {})", x.human_readable(), y.human_readable()),
@@ -83,30 +97,199 @@ impl<Variable: HumanReadable> HumanReadable for Term<Variable> {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AccuracyCounts {
+    pub true_count: u32,
+    pub false_count: u32,
+    pub unknown_count: u32,
+}
+
+impl AccuracyCounts {
+    // over the contexts where the term is known, None when it is known in none
+    pub fn accuracy(&self) -> Option<f64> {
+        let known_count = self.true_count + self.false_count;
+        (known_count > 0).then(|| self.true_count as f64 / known_count as f64)
+    }
+}
+
+// unknown contexts are left out, see compute_accuracy_counts to count them
 pub fn compute_accuracy<Context, Variable: EvaluateVariableIn<Context>, T: EvaluateIn<Context>>(
     t: &T,
     contexts: impl Iterator<Item = Context>,
-) -> f64 {
-    let mut correct = 0;
-    let mut total = 0;
+) -> Option<f64> {
+    compute_accuracy_counts(t, contexts).accuracy()
+}
+
+pub fn compute_accuracy_counts<Context, T: EvaluateIn<Context>>(
+    t: &T,
+    contexts: impl Iterator<Item = Context>,
+) -> AccuracyCounts {
+    let mut counts = AccuracyCounts::default();
     for context in contexts {
-        if t.evaluate_in(&context) {
-            correct += 1;
+        match t.evaluate_in(&context) {
+            TruthValue::True => counts.true_count += 1,
+            TruthValue::False => counts.false_count += 1,
+            TruthValue::Unknown => counts.unknown_count += 1,
         }
-        total += 1;
     }
-    correct as f64 / total as f64
+    counts
 }
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub struct TruthTable<Variable: Ord> {
     variables: BTreeSet<Rc<Variable>>,
-    results: Vec<bool>,
+    // one bit per combination, packed 64 to a word, the unused bits of a single word are zero
+    // so that equal tables hash the same
+    results: Vec<u64>,
+}
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+impl<Variable: Ord> TruthTable<Variable> {
+    fn from_fn(
+        variables: BTreeSet<Rc<Variable>>,
+        mut result: impl FnMut(usize) -> bool,
+    ) -> TruthTable<Variable> {
+        let combinations = 1usize << variables.len();
+        let mut results = vec![0u64; combinations.div_ceil(WORD_BITS)];
+        for combination_index in 0..combinations {
+            if result(combination_index) {
+                results[combination_index / WORD_BITS] |= 1 << (combination_index % WORD_BITS);
+            }
+        }
+        TruthTable { variables, results }
+    }
+    pub fn variables(&self) -> &BTreeSet<Rc<Variable>> {
+        &self.variables
+    }
+    // bit i of the combination index is the value of the i-th variable, in order
+    pub fn result(&self, combination_index: usize) -> bool {
+        (self.results[combination_index / WORD_BITS] >> (combination_index % WORD_BITS)) & 1 == 1
+    }
+    // how many combinations are true
+    pub fn count_ones(&self) -> u64 {
+        self.results
+            .iter()
+            .map(|word| word.count_ones() as u64)
+            .sum()
+    }
+    // the same function over more variables, which must include the current ones
+    pub fn extend_to(&self, variables: &BTreeSet<Rc<Variable>>) -> TruthTable<Variable> {
+        if *variables == self.variables {
+            return self.clone();
+        }
+        let positions: Vec<usize> = self
+            .variables
+            .iter()
+            .map(|variable| {
+                variables
+                    .iter()
+                    .position(|other| other == variable)
+                    .expect("variables must include the ones of the table")
+            })
+            .collect();
+        TruthTable::from_fn(variables.clone(), |combination_index| {
+            let own_combination_index = positions.iter().enumerate().fold(
+                0,
+                |own_combination_index, (variable_index, position)| {
+                    own_combination_index
+                        | (((combination_index >> position) & 1) << variable_index)
+                },
+            );
+            self.result(own_combination_index)
+        })
+    }
+    // word by word, after bringing both tables to the union of their variables
+    fn combine(
+        &self,
+        other: &TruthTable<Variable>,
+        operation: impl Fn(u64, u64) -> u64,
+    ) -> TruthTable<Variable> {
+        if self.variables != other.variables {
+            let variables: BTreeSet<Rc<Variable>> =
+                self.variables.union(&other.variables).cloned().collect();
+            return self
+                .extend_to(&variables)
+                .combine(&other.extend_to(&variables), operation);
+        }
+        TruthTable {
+            variables: self.variables.clone(),
+            results: self
+                .results
+                .iter()
+                .zip(other.results.iter())
+                .map(|(x, y)| operation(*x, *y))
+                .collect(),
+        }
+    }
+}
+
+impl<Variable: Ord> Clone for TruthTable<Variable> {
+    fn clone(&self) -> Self {
+        TruthTable {
+            variables: self.variables.clone(),
+            results: self.results.clone(),
+        }
+    }
+}
+
+impl<Variable: Ord> Not for &TruthTable<Variable> {
+    type Output = TruthTable<Variable>;
+    fn not(self) -> TruthTable<Variable> {
+        let combinations = 1usize << self.variables.len();
+        let used_bits = if combinations < WORD_BITS {
+            (1 << combinations) - 1
+        } else {
+            u64::MAX
+        };
+        TruthTable {
+            variables: self.variables.clone(),
+            results: self.results.iter().map(|word| !word & used_bits).collect(),
+        }
+    }
+}
+
+impl<Variable: Ord> BitAnd for &TruthTable<Variable> {
+    type Output = TruthTable<Variable>;
+    fn bitand(self, other: &TruthTable<Variable>) -> TruthTable<Variable> {
+        self.combine(other, |x, y| x & y)
+    }
+}
+
+impl<Variable: Ord> BitOr for &TruthTable<Variable> {
+    type Output = TruthTable<Variable>;
+    fn bitor(self, other: &TruthTable<Variable>) -> TruthTable<Variable> {
+        self.combine(other, |x, y| x | y)
+    }
+}
+
+impl<Variable: Ord> BitXor for &TruthTable<Variable> {
+    type Output = TruthTable<Variable>;
+    fn bitxor(self, other: &TruthTable<Variable>) -> TruthTable<Variable> {
+        self.combine(other, |x, y| x ^ y)
+    }
+}
+
+// like experiment7 create_new_level_terms, but straight on the tables of the existing terms
+pub fn create_new_level_truth_tables<Variable: Ord + Hash>(
+    existing_truth_tables: &HashSet<TruthTable<Variable>>,
+) -> HashSet<TruthTable<Variable>> {
+    let mut new_truth_tables = HashSet::new();
+    for left in existing_truth_tables {
+        new_truth_tables.insert(!left);
+        for right in existing_truth_tables {
+            new_truth_tables.insert(left & right);
+            new_truth_tables.insert(left | right);
+        }
+    }
+    new_truth_tables
 }
+
 impl<Variable: Ord + Hash> Term<Variable> {
     fn get_variables(&self, variables: &mut BTreeSet<Rc<Variable>>) {
         use Expr::*;
         match &self.0 {
+            False | True => {}
             Var(variable) => {
                 variables.insert(variable.clone());
             }
@@ -129,15 +312,12 @@ impl<Variable: Ord + Hash> Term<Variable> {
             .enumerate()
             .map(|(index, variable)| (variable.clone(), index))
             .collect();
-        let combinations = (2 as u32).pow(variables.len() as u32) as usize;
-        let mut results = vec![false; combinations];
-        for combination_index in 0..combinations {
-            results[combination_index] = self.evaluate_in(&TruthTableContext {
+        TruthTable::from_fn(variables, |combination_index| {
+            self.evaluate_in(&TruthTableContext {
                 variables_index,
                 combination_index,
-            })
-        }
-        TruthTable { variables, results }
+            }) == TruthValue::True
+        })
     }
 }
 
@@ -147,33 +327,55 @@ struct TruthTableContext<'a, Variable> {
 }
 
 impl<'a, Variable: Eq + Hash> EvaluateVariableIn<TruthTableContext<'a, Variable>> for Variable {
-    fn evaluate_variable_in(&self, context: &TruthTableContext<Variable>) -> bool {
+    fn evaluate_variable_in(&self, context: &TruthTableContext<Variable>) -> TruthValue {
         let variable_index = context.variables_index.get(self).unwrap();
-        (context.combination_index >> variable_index) & 1 == 1
+        ((context.combination_index >> variable_index) & 1 == 1).into()
     }
 }
 
+// variables missing from the map are unknown
 impl<'a, Variable: Eq + Hash> EvaluateVariableIn<HashMap<Variable, bool>> for Variable {
-    fn evaluate_variable_in(&self, context: &HashMap<Variable, bool>) -> bool {
-        *context.get(self).unwrap()
+    fn evaluate_variable_in(&self, context: &HashMap<Variable, bool>) -> TruthValue {
+        context
+            .get(self)
+            .map_or(TruthValue::Unknown, |value| (*value).into())
     }
 }
 
+// the table no longer knows the shape of the term, so with unknown variables the result is
+// known only when every way of filling them in gives the same one (a ∨ ¬a is always true)
 impl<Context, Variable: Ord + EvaluateVariableIn<Context>> EvaluateIn<Context>
     for TruthTable<Variable>
 {
-    fn evaluate_in(&self, context: &Context) -> bool {
-        let combination_index = self.variables.iter().enumerate().fold(
-            0,
-            |combination_index, (variable_index, variable)| {
-                if variable.evaluate_variable_in(context) {
-                    combination_index | (1 << variable_index)
-                } else {
-                    combination_index
-                }
-            },
-        );
-        self.results[combination_index]
+    fn evaluate_in(&self, context: &Context) -> TruthValue {
+        let mut known_combination_index = 0;
+        let mut unknown_variable_indices = Vec::new();
+        for (variable_index, variable) in self.variables.iter().enumerate() {
+            match variable.evaluate_variable_in(context) {
+                TruthValue::True => known_combination_index |= 1 << variable_index,
+                TruthValue::False => {}
+                TruthValue::Unknown => unknown_variable_indices.push(variable_index),
+            }
+        }
+        let mut results = (0..1usize << unknown_variable_indices.len()).map(|filling| {
+            let combination_index = unknown_variable_indices.iter().enumerate().fold(
+                known_combination_index,
+                |combination_index, (bit, variable_index)| {
+                    if (filling >> bit) & 1 == 1 {
+                        combination_index | (1 << variable_index)
+                    } else {
+                        combination_index
+                    }
+                },
+            );
+            self.result(combination_index)
+        });
+        let first = results.next().unwrap();
+        if results.all(|result| result == first) {
+            first.into()
+        } else {
+            TruthValue::Unknown
+        }
     }
 }
 
@@ -287,3 +489,116 @@ fn test_evaluate_truth_table() {
         true
     );
 }
+
+#[test]
+fn test_kleene_evaluation_with_unknown_variables() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let a_and_b = term_repository.and(&a, &b);
+    let a_or_b = term_repository.or(&a, &b);
+    let not_a = term_repository.not(&a);
+    let a_or_not_a = term_repository.or(&a, &not_a);
+    let only_b_false = HashMap::from([('b', false)]);
+    let only_b_true = HashMap::from([('b', true)]);
+    assert_eq!(a.evaluate_in(&only_b_false), TruthValue::Unknown);
+    assert_eq!(not_a.evaluate_in(&only_b_false), TruthValue::Unknown);
+    assert_eq!(a_and_b.evaluate_in(&only_b_false), false);
+    assert_eq!(a_and_b.evaluate_in(&only_b_true), TruthValue::Unknown);
+    assert_eq!(a_or_b.evaluate_in(&only_b_true), true);
+    assert_eq!(a_or_b.evaluate_in(&only_b_false), TruthValue::Unknown);
+    assert_eq!(a_or_not_a.evaluate_in(&only_b_false), TruthValue::Unknown);
+    let a_and_b_table = a_and_b.compute_truth_table();
+    assert_eq!(a_and_b_table.evaluate_in(&only_b_false), false);
+    assert_eq!(a_and_b_table.evaluate_in(&only_b_true), TruthValue::Unknown);
+    assert_eq!(
+        a_or_not_a.compute_truth_table().evaluate_in(&only_b_false),
+        true
+    );
+    let counts = compute_accuracy_counts(a_and_b.as_ref(), [only_b_false, only_b_true].into_iter());
+    assert_eq!(
+        counts,
+        AccuracyCounts {
+            true_count: 0,
+            false_count: 1,
+            unknown_count: 1
+        }
+    );
+    assert_eq!(counts.accuracy(), Some(0.0));
+    let counts =
+        compute_accuracy_counts(a_and_b.as_ref(), [HashMap::from([('b', true)])].into_iter());
+    assert_eq!(counts.accuracy(), None);
+}
+
+#[test]
+fn test_truth_table_operations() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let a_table = a.compute_truth_table();
+    let b_table = b.compute_truth_table();
+    let a_and_b = term_repository.and(&a, &b);
+    let a_or_b = term_repository.or(&a, &b);
+    let not_a = term_repository.not(&a);
+    let a_iff_b = term_repository.biconditional(&a, &b);
+    assert_eq!(&a_table & &b_table, a_and_b.compute_truth_table());
+    assert_eq!(&a_table | &b_table, a_or_b.compute_truth_table());
+    assert_eq!(!&a_table, not_a.compute_truth_table());
+    assert_eq!(!&(&a_table ^ &b_table), a_iff_b.compute_truth_table());
+    assert_eq!(&a_table & &a_table, a_table);
+    assert_eq!(a_and_b.compute_truth_table().count_ones(), 1);
+    assert_eq!(a_or_b.compute_truth_table().count_ones(), 3);
+    let a_over_a_and_b = a_table.extend_to(a_and_b.compute_truth_table().variables());
+    assert_eq!(a_over_a_and_b.count_ones(), 2);
+    assert_eq!(a_over_a_and_b, &a_table | &(&b_table & &!&b_table));
+    // more combinations than fit in a word
+    let variables: Vec<_> = ('a'..='g')
+        .map(|variable| term_repository.var(&variable_repository.get_or_create(variable)))
+        .collect();
+    let all = variables
+        .iter()
+        .map(|variable| variable.compute_truth_table())
+        .reduce(|all, table| &all & &table)
+        .unwrap();
+    assert_eq!(all.count_ones(), 1);
+    assert_eq!((!&all).count_ones(), 127);
+    assert!(all.result(127));
+    let all_term = variables
+        .iter()
+        .cloned()
+        .reduce(|all, variable| term_repository.and(&all, &variable))
+        .unwrap();
+    assert_eq!(all, all_term.compute_truth_table());
+    assert_eq!(!&!&all, all);
+}
+
+#[test]
+fn test_level_truth_tables_match_term_truth_tables() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let c = term_repository.var(&variable_repository.get_or_create('c'));
+    let input_terms = [a, b, c];
+    let mut level_1_terms = HashSet::new();
+    for left in &input_terms {
+        level_1_terms.insert(term_repository.not(left));
+        for right in &input_terms {
+            level_1_terms.insert(term_repository.and(left, right));
+            level_1_terms.insert(term_repository.or(left, right));
+        }
+    }
+    let input_truth_tables: HashSet<_> = input_terms
+        .iter()
+        .map(|term| term.compute_truth_table())
+        .collect();
+    assert_eq!(
+        create_new_level_truth_tables(&input_truth_tables),
+        level_1_terms
+            .iter()
+            .map(|term| term.compute_truth_table())
+            .collect()
+    );
+}
@@ -17,11 +17,12 @@ pub struct CharacterWindow {
 }
 
 impl EvaluateVariableIn<CharacterWindow> for CharacterInWindow {
-    fn evaluate_variable_in(&self, context: &CharacterWindow) -> bool {
+    // before the start of the data the character is unknown, not a different one
+    fn evaluate_variable_in(&self, context: &CharacterWindow) -> TruthValue {
         if self.negative_offset > context.index {
-            return false;
+            return TruthValue::Unknown;
         }
-        self.character == context.data[context.index - self.negative_offset]
+        (self.character == context.data[context.index - self.negative_offset]).into()
     }
 }
 
@@ -58,3 +59,10 @@ pub fn contexts_from_data(data: Rc<Vec<char>>) -> impl Iterator<Item = Character
         index,
     })
 }
+
+// windows of each document on its own, none of them spans two documents
+pub fn contexts_from_documents(
+    documents: impl IntoIterator<Item = Rc<Vec<char>>>,
+) -> impl Iterator<Item = CharacterWindow> {
+    documents.into_iter().flat_map(contexts_from_data)
+}
@@ -0,0 +1,461 @@
+use super::boolean_algebra::*;
+use super::rc_repository::*;
+use std::{
+    collections::{BTreeSet, HashSet},
+    hash::Hash,
+    rc::Rc,
+};
+
+// above this many variables the exact cover search of Quine–McCluskey gets too slow
+pub const QUINE_MCCLUSKEY_MAX_VARIABLES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Off,
+    On,
+    DontCare,
+}
+
+// a product of literals over the table variables, the bits in mask are the variables left out
+// (they are always zero in value)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Cube {
+    value: usize,
+    mask: usize,
+}
+
+impl Cube {
+    fn row(combination_index: usize) -> Self {
+        Cube {
+            value: combination_index,
+            mask: 0,
+        }
+    }
+    fn covers(&self, combination_index: usize) -> bool {
+        combination_index & !self.mask == self.value
+    }
+    fn contains(&self, other: &Cube) -> bool {
+        self.mask & other.mask == other.mask && other.value & !self.mask == self.value
+    }
+    fn rows(&self) -> impl Iterator<Item = usize> {
+        let Cube { value, mask } = *self;
+        // every subset of the mask, from the full one down to the empty one
+        let mut subset = Some(mask);
+        std::iter::from_fn(move || {
+            let current = subset?;
+            subset = if current == 0 {
+                None
+            } else {
+                Some((current - 1) & mask)
+            };
+            Some(value | current)
+        })
+    }
+    fn literal_count(&self, variable_count: usize) -> usize {
+        variable_count - self.mask.count_ones() as usize
+    }
+}
+
+// fewer products first, then fewer literals
+fn cost(cover: &[Cube], variable_count: usize) -> (usize, usize) {
+    (
+        cover.len(),
+        cover
+            .iter()
+            .map(|cube| cube.literal_count(variable_count))
+            .sum(),
+    )
+}
+
+fn classify_rows<Variable: Ord>(
+    truth_table: &TruthTable<Variable>,
+    dont_cares: &[usize],
+) -> Vec<Row> {
+    let dont_cares: HashSet<usize> = dont_cares.iter().cloned().collect();
+    (0..1usize << truth_table.variables().len())
+        .map(|combination_index| {
+            if dont_cares.contains(&combination_index) {
+                Row::DontCare
+            } else if truth_table.result(combination_index) {
+                Row::On
+            } else {
+                Row::Off
+            }
+        })
+        .collect()
+}
+
+// https://en.wikipedia.org/wiki/Quine%E2%80%93McCluskey_algorithm
+// merges cubes differing in a single variable until no more merges are possible
+fn prime_implicants(rows: &[Row], variable_count: usize) -> Vec<Cube> {
+    let mut current: BTreeSet<Cube> = (0..rows.len())
+        .filter(|combination_index| rows[*combination_index] != Row::Off)
+        .map(Cube::row)
+        .collect();
+    let mut primes: Vec<Cube> = Vec::new();
+    while !current.is_empty() {
+        let mut next = BTreeSet::new();
+        let mut merged = HashSet::new();
+        for cube in &current {
+            for bit in (0..variable_count).map(|variable_index| 1 << variable_index) {
+                if (cube.mask | cube.value) & bit != 0 {
+                    continue;
+                }
+                let other = Cube {
+                    value: cube.value | bit,
+                    mask: cube.mask,
+                };
+                if current.contains(&other) {
+                    next.insert(Cube {
+                        value: cube.value,
+                        mask: cube.mask | bit,
+                    });
+                    merged.insert(*cube);
+                    merged.insert(other);
+                }
+            }
+        }
+        primes.extend(current.iter().filter(|cube| !merged.contains(*cube)));
+        current = next;
+    }
+    // primes made only of don't cares are never needed
+    primes.retain(|prime| prime.rows().any(|row| rows[row] == Row::On));
+    primes
+}
+
+// branch and bound on the prime implicant chart, always branching on the row with the fewest
+// primes covering it, so essential primes are taken first
+fn minimum_cover(primes: &[Cube], rows: &[Row], variable_count: usize) -> Vec<Cube> {
+    fn search(
+        uncovered: &BTreeSet<usize>,
+        primes: &[Cube],
+        variable_count: usize,
+        chosen: &mut Vec<Cube>,
+        best: &mut Option<Vec<Cube>>,
+    ) {
+        if uncovered.is_empty() {
+            if best
+                .as_ref()
+                .is_none_or(|best| cost(chosen, variable_count) < cost(best, variable_count))
+            {
+                *best = Some(chosen.clone());
+            }
+            return;
+        }
+        if let Some(best) = best {
+            if chosen.len() + 1 > best.len() {
+                return;
+            }
+        }
+        let covering = |row: usize| primes.iter().filter(move |prime| prime.covers(row));
+        let row = *uncovered
+            .iter()
+            .min_by_key(|row| covering(**row).count())
+            .unwrap();
+        for prime in covering(row) {
+            chosen.push(*prime);
+            let still_uncovered = uncovered
+                .iter()
+                .filter(|row| !prime.covers(**row))
+                .cloned()
+                .collect();
+            search(&still_uncovered, primes, variable_count, chosen, best);
+            chosen.pop();
+        }
+    }
+    let uncovered = (0..rows.len())
+        .filter(|combination_index| rows[*combination_index] == Row::On)
+        .collect();
+    let mut best = None;
+    search(
+        &uncovered,
+        primes,
+        variable_count,
+        &mut Vec::new(),
+        &mut best,
+    );
+    best.unwrap_or_default()
+}
+
+// https://en.wikipedia.org/wiki/Espresso_heuristic_logic_minimizer
+// starts from the rows and improves the cover with expand, irredundant and reduce steps until
+// the cost stops decreasing, the result is irredundant but not necessarily minimum
+fn espresso(rows: &[Row], variable_count: usize) -> Vec<Cube> {
+    let cover: Vec<Cube> = (0..rows.len())
+        .filter(|combination_index| rows[*combination_index] == Row::On)
+        .map(Cube::row)
+        .collect();
+    let mut cover = irredundant(expand(cover, rows, variable_count), rows);
+    loop {
+        let candidate = irredundant(expand(reduce(&cover, rows), rows, variable_count), rows);
+        if cost(&candidate, variable_count) >= cost(&cover, variable_count) {
+            return cover;
+        }
+        cover = candidate;
+    }
+}
+
+// makes each cube as large as possible without covering off rows, dropping cubes contained in
+// already expanded ones
+fn expand(mut cover: Vec<Cube>, rows: &[Row], variable_count: usize) -> Vec<Cube> {
+    cover.sort_by_key(|cube| cube.literal_count(variable_count));
+    let mut expanded: Vec<Cube> = Vec::new();
+    for mut cube in cover {
+        if expanded.iter().any(|other| other.contains(&cube)) {
+            continue;
+        }
+        for bit in (0..variable_count).map(|variable_index| 1 << variable_index) {
+            if cube.mask & bit != 0 {
+                continue;
+            }
+            let raised = Cube {
+                value: cube.value & !bit,
+                mask: cube.mask | bit,
+            };
+            if raised.rows().all(|row| rows[row] != Row::Off) {
+                cube = raised;
+            }
+        }
+        expanded.retain(|other| !cube.contains(other));
+        expanded.push(cube);
+    }
+    expanded
+}
+
+// removes cubes whose on rows are all covered by the other cubes, smallest cubes first
+fn irredundant(mut cover: Vec<Cube>, rows: &[Row]) -> Vec<Cube> {
+    cover.sort_by_key(|cube| cube.mask.count_ones());
+    let mut index = 0;
+    while index < cover.len() {
+        let cube = cover[index];
+        let redundant = cube.rows().filter(|row| rows[*row] == Row::On).all(|row| {
+            cover
+                .iter()
+                .enumerate()
+                .any(|(other_index, other)| other_index != index && other.covers(row))
+        });
+        if redundant {
+            cover.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+    cover
+}
+
+// shrinks each cube to the smallest one containing the on rows only it covers, so that the
+// next expand can grow it in a different direction
+fn reduce(cover: &[Cube], rows: &[Row]) -> Vec<Cube> {
+    let mut reduced = cover.to_vec();
+    for index in 0..reduced.len() {
+        let cube = reduced[index];
+        let mut only_covered_here = cube.rows().filter(|row| {
+            rows[*row] == Row::On
+                && !reduced
+                    .iter()
+                    .enumerate()
+                    .any(|(other_index, other)| other_index != index && other.covers(*row))
+        });
+        if let Some(first) = only_covered_here.next() {
+            let mask = only_covered_here.fold(0, |mask, row| mask | (row ^ first));
+            reduced[index] = Cube {
+                value: first & !mask,
+                mask,
+            };
+        }
+    }
+    reduced
+}
+
+impl<Variable: Ord + Hash> RcRepository<Term<Variable>> {
+    // don't cares are combination indices (as in TruthTable::result) whose result does not matter
+    pub fn quine_mccluskey(
+        &mut self,
+        truth_table: &TruthTable<Variable>,
+        dont_cares: &[usize],
+    ) -> Rc<Term<Variable>> {
+        let variable_count = truth_table.variables().len();
+        let rows = classify_rows(truth_table, dont_cares);
+        let primes = prime_implicants(&rows, variable_count);
+        let cover = minimum_cover(&primes, &rows, variable_count);
+        self.sum_of_products(truth_table, cover)
+    }
+
+    pub fn espresso(
+        &mut self,
+        truth_table: &TruthTable<Variable>,
+        dont_cares: &[usize],
+    ) -> Rc<Term<Variable>> {
+        let variable_count = truth_table.variables().len();
+        let rows = classify_rows(truth_table, dont_cares);
+        let cover = espresso(&rows, variable_count);
+        self.sum_of_products(truth_table, cover)
+    }
+
+    // exact when the table is small enough, heuristic otherwise
+    pub fn minimal_sum_of_products(
+        &mut self,
+        truth_table: &TruthTable<Variable>,
+        dont_cares: &[usize],
+    ) -> Rc<Term<Variable>> {
+        if truth_table.variables().len() <= QUINE_MCCLUSKEY_MAX_VARIABLES {
+            self.quine_mccluskey(truth_table, dont_cares)
+        } else {
+            self.espresso(truth_table, dont_cares)
+        }
+    }
+
+    fn sum_of_products(
+        &mut self,
+        truth_table: &TruthTable<Variable>,
+        mut cover: Vec<Cube>,
+    ) -> Rc<Term<Variable>> {
+        let variables: Vec<Rc<Variable>> = truth_table.variables().iter().cloned().collect();
+        cover.sort();
+        let products: Vec<Rc<Term<Variable>>> = cover
+            .into_iter()
+            .map(|cube| {
+                let literals: Vec<Rc<Term<Variable>>> = variables
+                    .iter()
+                    .enumerate()
+                    .filter(|(variable_index, _)| cube.mask & (1 << variable_index) == 0)
+                    .map(|(variable_index, variable)| {
+                        let variable = self.var(variable);
+                        if cube.value & (1 << variable_index) != 0 {
+                            variable
+                        } else {
+                            self.not(&variable)
+                        }
+                    })
+                    .collect();
+                literals
+                    .into_iter()
+                    .reduce(|product, literal| self.and(&product, &literal))
+                    .unwrap_or_else(|| self.constant(true))
+            })
+            .collect();
+        products
+            .into_iter()
+            .reduce(|sum, product| self.or(&sum, &product))
+            .unwrap_or_else(|| self.constant(false))
+    }
+}
+
+#[test]
+fn test_minimal_sum_of_products() {
+    use super::utils::*;
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let not_a = term_repository.not(&a);
+    let not_b = term_repository.not(&b);
+    let a_iff_b = term_repository.biconditional(&b, &a);
+    let a_iff_b_table = a_iff_b.compute_truth_table();
+    assert_eq!(
+        term_repository
+            .quine_mccluskey(&a_iff_b_table, &[])
+            .human_readable(),
+        "((¬a ∧ ¬b) ∨ (a ∧ b))"
+    );
+    assert_eq!(
+        term_repository
+            .espresso(&a_iff_b_table, &[])
+            .human_readable(),
+        "((¬a ∧ ¬b) ∨ (a ∧ b))"
+    );
+    let a_and_b = term_repository.and(&a, &b);
+    let a_and_not_b = term_repository.and(&a, &not_b);
+    let not_a_and_b = term_repository.and(&not_a, &b);
+    let a_or_b = term_repository.or(&a_and_b, &a_and_not_b);
+    let a_or_b = term_repository.or(&a_or_b, &not_a_and_b);
+    let a_or_b_table = a_or_b.compute_truth_table();
+    assert_eq!(
+        term_repository
+            .minimal_sum_of_products(&a_or_b_table, &[])
+            .human_readable(),
+        "(a ∨ b)"
+    );
+    assert_eq!(
+        term_repository
+            .espresso(&a_or_b_table, &[])
+            .human_readable(),
+        "(a ∨ b)"
+    );
+    // whatever happens when only b is true
+    let a_and_b_table = a_and_b.compute_truth_table();
+    assert_eq!(
+        term_repository
+            .quine_mccluskey(&a_and_b_table, &[0b10])
+            .human_readable(),
+        "b"
+    );
+    assert_eq!(
+        term_repository
+            .espresso(&a_and_b_table, &[0b10])
+            .human_readable(),
+        "b"
+    );
+    let a_and_not_a = term_repository.and(&a, &not_a);
+    let a_or_not_a = term_repository.or(&a, &not_a);
+    assert_eq!(
+        term_repository
+            .minimal_sum_of_products(&a_and_not_a.compute_truth_table(), &[])
+            .human_readable(),
+        "⊥"
+    );
+    assert_eq!(
+        term_repository
+            .minimal_sum_of_products(&a_or_not_a.compute_truth_table(), &[])
+            .human_readable(),
+        "⊤"
+    );
+}
+
+#[test]
+fn test_espresso_on_larger_tables() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let variables: Vec<_> = ('a'..='l')
+        .map(|variable| term_repository.var(&variable_repository.get_or_create(variable)))
+        .collect();
+    let products: Vec<_> = variables
+        .chunks(2)
+        .map(|pair| term_repository.and(&pair[0], &pair[1]))
+        .collect();
+    let term = products
+        .into_iter()
+        .reduce(|sum, product| term_repository.or(&sum, &product))
+        .unwrap();
+    let table = term.compute_truth_table();
+    assert!(table.variables().len() > QUINE_MCCLUSKEY_MAX_VARIABLES);
+    assert_eq!(term_repository.minimal_sum_of_products(&table, &[]), term);
+}
+
+#[test]
+fn test_covers_of_every_three_variable_function() {
+    let variable_count = 3;
+    for function in 0..256usize {
+        let rows: Vec<Row> = (0..8)
+            .map(|row| {
+                if (function >> row) & 1 == 1 {
+                    Row::On
+                } else {
+                    Row::Off
+                }
+            })
+            .collect();
+        let exact = minimum_cover(
+            &prime_implicants(&rows, variable_count),
+            &rows,
+            variable_count,
+        );
+        let heuristic = espresso(&rows, variable_count);
+        for cover in [&exact, &heuristic] {
+            for (row, kind) in rows.iter().enumerate() {
+                assert_eq!(cover.iter().any(|cube| cube.covers(row)), *kind == Row::On);
+            }
+        }
+        assert!(cost(&exact, variable_count) <= cost(&heuristic, variable_count));
+    }
+}
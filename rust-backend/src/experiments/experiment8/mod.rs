@@ -1,6 +1,10 @@
+pub mod algebraic_normal_form;
+mod binary_decision_diagram;
 mod boolean_algebra;
 mod character_window;
+mod logic_minimization;
 mod rc_repository;
+mod simplifier;
 mod utils;
 
 mod experiments {
@@ -18,7 +22,7 @@ mod experiments {
         let alphabet = derive_alphabet_from_data(text.chars());
         let mut v: RcRepository<CharacterInWindow> = RcRepository::new();
         let mut t: RcRepository<Term<CharacterInWindow>> = RcRepository::new();
-        let mut accuracies: Vec<(char, char, f64)> = Vec::new();
+        let mut accuracies: Vec<(char, char, Option<f64>)> = Vec::new();
         for current_character in alphabet.iter() {
             for previous_character in alphabet.iter() {
                 let current_character_term = t.var(&v.character_in_window(0, *current_character));
@@ -37,4 +41,23 @@ mod experiments {
         }
         dbg!(accuracies);
     }
+
+    #[test]
+    fn windows_do_not_span_documents() {
+        let mut v: RcRepository<CharacterInWindow> = RcRepository::new();
+        let mut t: RcRepository<Term<CharacterInWindow>> = RcRepository::new();
+        let previous_is_b = t.var(&v.character_in_window(1, 'b'));
+        let documents = ["ab", "ba"].map(|text| Rc::new(text.chars().collect::<Vec<char>>()));
+        let counts =
+            compute_accuracy_counts(previous_is_b.as_ref(), contexts_from_documents(documents));
+        // the first window of each document has no previous character
+        assert_eq!(
+            counts,
+            AccuracyCounts {
+                true_count: 1,
+                false_count: 1,
+                unknown_count: 2
+            }
+        );
+    }
 }
@@ -0,0 +1,215 @@
+use super::boolean_algebra::*;
+use super::rc_repository::*;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    rc::Rc,
+};
+
+// a single bottom-up pass: the operands are simplified first and every rewrite builds its result
+// through the same rules (De Morgan calls them again on the new operands), so the result of a
+// pass is left as it is by another one
+impl<Variable: Ord + Hash> RcRepository<Term<Variable>> {
+    pub fn simplify(&mut self, term: &Rc<Term<Variable>>) -> Rc<Term<Variable>> {
+        self.simplify_memoized(term, &mut HashMap::new())
+    }
+
+    // terms are shared, each one is simplified once
+    fn simplify_memoized(
+        &mut self,
+        term: &Rc<Term<Variable>>,
+        simplified: &mut HashMap<Rc<Term<Variable>>, Rc<Term<Variable>>>,
+    ) -> Rc<Term<Variable>> {
+        if let Some(existing) = simplified.get(term) {
+            return existing.clone();
+        }
+        let result = match &term.0 {
+            Expr::False | Expr::True | Expr::Var(_) => term.clone(),
+            Expr::Not(x) => {
+                let x = self.simplify_memoized(x, simplified);
+                self.simplified_not(&x)
+            }
+            Expr::And(x, y) => {
+                let x = self.simplify_memoized(x, simplified);
+                let y = self.simplify_memoized(y, simplified);
+                self.simplified_and_of(&x, &y)
+            }
+            Expr::Or(x, y) => {
+                let x = self.simplify_memoized(x, simplified);
+                let y = self.simplify_memoized(y, simplified);
+                self.simplified_or_of(&x, &y)
+            }
+        };
+        simplified.insert(term.clone(), result.clone());
+        result
+    }
+
+    // the following assume their operands are already simplified
+
+    fn simplified_not(&mut self, x: &Rc<Term<Variable>>) -> Rc<Term<Variable>> {
+        match &x.0 {
+            Expr::False => self.constant(true),
+            Expr::True => self.constant(false),
+            // double negation
+            Expr::Not(y) => y.clone(),
+            _ => self.not(x),
+        }
+    }
+
+    fn simplified_and_of(
+        &mut self,
+        x: &Rc<Term<Variable>>,
+        y: &Rc<Term<Variable>>,
+    ) -> Rc<Term<Variable>> {
+        match (&x.0, &y.0) {
+            // constant folding
+            (Expr::False, _) | (_, Expr::False) => self.constant(false),
+            (Expr::True, _) => y.clone(),
+            (_, Expr::True) => x.clone(),
+            // idempotence
+            _ if x == y => x.clone(),
+            // complementation
+            _ if is_negation_of(x, y) || is_negation_of(y, x) => self.constant(false),
+            // absorption
+            (_, Expr::Or(p, q)) if p == x || q == x => x.clone(),
+            (Expr::Or(p, q), _) if p == y || q == y => y.clone(),
+            // De Morgan, in the direction that drops a negation
+            (Expr::Not(p), Expr::Not(q)) => {
+                let p_or_q = self.simplified_or_of(&p.clone(), &q.clone());
+                self.simplified_not(&p_or_q)
+            }
+            _ if x > y => self.and(y, x),
+            _ => self.and(x, y),
+        }
+    }
+
+    fn simplified_or_of(
+        &mut self,
+        x: &Rc<Term<Variable>>,
+        y: &Rc<Term<Variable>>,
+    ) -> Rc<Term<Variable>> {
+        match (&x.0, &y.0) {
+            // constant folding
+            (Expr::True, _) | (_, Expr::True) => self.constant(true),
+            (Expr::False, _) => y.clone(),
+            (_, Expr::False) => x.clone(),
+            // idempotence
+            _ if x == y => x.clone(),
+            // complementation
+            _ if is_negation_of(x, y) || is_negation_of(y, x) => self.constant(true),
+            // absorption
+            (_, Expr::And(p, q)) if p == x || q == x => x.clone(),
+            (Expr::And(p, q), _) if p == y || q == y => y.clone(),
+            // De Morgan, in the direction that drops a negation
+            (Expr::Not(p), Expr::Not(q)) => {
+                let p_and_q = self.simplified_and_of(&p.clone(), &q.clone());
+                self.simplified_not(&p_and_q)
+            }
+            _ if x > y => self.or(y, x),
+            _ => self.or(x, y),
+        }
+    }
+}
+
+fn is_negation_of<Variable: Eq>(x: &Rc<Term<Variable>>, y: &Rc<Term<Variable>>) -> bool {
+    matches!(&x.0, Expr::Not(z) if z == y)
+}
+
+// like experiment7, but terms that simplify to an existing one (a ∧ a, ¬¬a) are left out
+pub fn create_new_level_terms<Variable: Ord + Hash>(
+    term_repository: &mut RcRepository<Term<Variable>>,
+    existing_terms: &HashSet<Rc<Term<Variable>>>,
+) -> HashSet<Rc<Term<Variable>>> {
+    let mut new_terms: HashSet<Rc<Term<Variable>>> = HashSet::new();
+    let mut simplified = HashMap::new();
+    for left in existing_terms {
+        let not_left = term_repository.not(left);
+        new_terms.insert(term_repository.simplify_memoized(&not_left, &mut simplified));
+        for right in existing_terms {
+            let left_and_right = term_repository.and(left, right);
+            new_terms.insert(term_repository.simplify_memoized(&left_and_right, &mut simplified));
+            let left_or_right = term_repository.or(left, right);
+            new_terms.insert(term_repository.simplify_memoized(&left_or_right, &mut simplified));
+        }
+    }
+    new_terms.retain(|term| !existing_terms.contains(term));
+    new_terms
+}
+
+#[test]
+fn test_simplify_rules() {
+    use super::utils::*;
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let not_a = term_repository.not(&a);
+    let not_b = term_repository.not(&b);
+    let not_not_a = term_repository.not(&not_a);
+    assert_eq!(term_repository.simplify(&not_not_a), a);
+    let a_and_a = term_repository.and(&a, &a);
+    assert_eq!(term_repository.simplify(&a_and_a), a);
+    let a_or_not_a = term_repository.or(&a, &not_a);
+    assert_eq!(term_repository.simplify(&a_or_not_a).human_readable(), "⊤");
+    let a_and_not_a = term_repository.and(&not_a, &a);
+    assert_eq!(term_repository.simplify(&a_and_not_a).human_readable(), "⊥");
+    let a_or_b = term_repository.or(&a, &b);
+    let a_and_a_or_b = term_repository.and(&a, &a_or_b);
+    assert_eq!(term_repository.simplify(&a_and_a_or_b), a);
+    let not_a_and_not_b = term_repository.and(&not_a, &not_b);
+    assert_eq!(
+        term_repository.simplify(&not_a_and_not_b).human_readable(),
+        "¬(a ∨ b)"
+    );
+    let b_and_a = term_repository.and(&b, &a);
+    let a_and_b = term_repository.and(&a, &b);
+    assert!(Rc::ptr_eq(
+        &term_repository.simplify(&b_and_a),
+        &term_repository.simplify(&a_and_b)
+    ));
+    let false_term = term_repository.constant(false);
+    let a_or_false = term_repository.or(&false_term, &a);
+    assert_eq!(term_repository.simplify(&a_or_false), a);
+    let a_iff_a = term_repository.biconditional(&a, &a);
+    assert_eq!(term_repository.simplify(&a_iff_a).human_readable(), "⊤");
+    let a_then_b = term_repository.conditional(&a, &b);
+    let not_a_then_b = term_repository.not(&a_then_b);
+    assert_eq!(
+        term_repository.simplify(&not_a_then_b).human_readable(),
+        "¬(b ∨ ¬a)"
+    );
+}
+
+#[test]
+fn test_simplified_levels_shrink_and_keep_meaning() {
+    let mut variable_repository: RcRepository<char> = RcRepository::new();
+    let mut term_repository: RcRepository<Term<char>> = RcRepository::new();
+    let a = term_repository.var(&variable_repository.get_or_create('a'));
+    let b = term_repository.var(&variable_repository.get_or_create('b'));
+    let input_terms = HashSet::from([a, b]);
+    let level_1_terms = create_new_level_terms(&mut term_repository, &input_terms);
+    // ¬a, ¬b, a ∧ b, a ∨ b
+    assert_eq!(level_1_terms.len(), 4);
+    let input_and_level_1_terms: HashSet<_> = input_terms.union(&level_1_terms).cloned().collect();
+    let mut unsimplified_level_2_terms = Vec::new();
+    for left in &input_and_level_1_terms {
+        unsimplified_level_2_terms.push(term_repository.not(left));
+        for right in &input_and_level_1_terms {
+            unsimplified_level_2_terms.push(term_repository.and(left, right));
+            unsimplified_level_2_terms.push(term_repository.or(left, right));
+        }
+    }
+    let level_2_terms = create_new_level_terms(&mut term_repository, &input_and_level_1_terms);
+    assert!(level_2_terms.len() < unsimplified_level_2_terms.len() / 2);
+    for term in unsimplified_level_2_terms {
+        let simplified = term_repository.simplify(&term);
+        assert!(Rc::ptr_eq(
+            &term_repository.simplify(&simplified),
+            &simplified
+        ));
+        for combination in 0..4 {
+            let context = HashMap::from([('a', combination & 1 == 1), ('b', combination & 2 == 2)]);
+            assert_eq!(simplified.evaluate_in(&context), term.evaluate_in(&context));
+        }
+    }
+}
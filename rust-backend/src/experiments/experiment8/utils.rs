@@ -4,6 +4,12 @@ pub trait HumanReadable {
     fn human_readable(&self) -> String;
 }
 
+impl HumanReadable for char {
+    fn human_readable(&self) -> String {
+        self.to_string()
+    }
+}
+
 pub fn write_csv_file<'a>(
     file_path: &str,
     columns: &[&str],
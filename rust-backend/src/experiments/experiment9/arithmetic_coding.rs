@@ -0,0 +1,223 @@
+use super::evaluation::PredictNextSymbol;
+use std::io::{Error, ErrorKind};
+
+// the coder works on 32 bit integers, in u64 so that range * frequency does not overflow
+const WHOLE: u64 = 1 << 32;
+const HALF: u64 = WHOLE / 2;
+const QUARTER: u64 = WHOLE / 4;
+// probabilities become integer frequencies out of about this total, way below QUARTER
+const FREQUENCY_SCALE: f64 = (1 << 16) as f64;
+// the symbol count is stored before the code, little endian
+const HEADER_LENGTH: usize = 8;
+
+// data coded with the distributions model predicts for each symbol from the ones preceding it,
+// decompress with the same model and alphabet gives it back; every symbol of data must be in
+// alphabet, which should hold every symbol the model predicts
+pub fn compress<Symbol: PartialEq>(
+    model: &impl PredictNextSymbol<Symbol>,
+    alphabet: &[Symbol],
+    data: &[Symbol],
+) -> Result<Vec<u8>, Error> {
+    let mut output = BitOutput {
+        bytes: (data.len() as u64).to_le_bytes().to_vec(),
+        bit_count: 0,
+    };
+    let mut low: u64 = 0;
+    let mut high: u64 = WHOLE - 1;
+    let mut pending: u32 = 0;
+    for index in 0..data.len() {
+        let symbol_index = alphabet
+            .iter()
+            .position(|symbol| *symbol == data[index])
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("symbol at {index} is not in the alphabet"),
+                )
+            })?;
+        let frequencies = frequencies(model, alphabet, &data[..index]);
+        let (from, to, total) = cumulative_range(&frequencies, symbol_index);
+        let range = high - low + 1;
+        high = low + range * to / total - 1;
+        low += range * from / total;
+        loop {
+            if high < HALF {
+                output.push_with_pending(false, &mut pending);
+            } else if low >= HALF {
+                output.push_with_pending(true, &mut pending);
+                low -= HALF;
+                high -= HALF;
+            } else if low >= QUARTER && high < HALF + QUARTER {
+                pending += 1;
+                low -= QUARTER;
+                high -= QUARTER;
+            } else {
+                break;
+            }
+            low *= 2;
+            high = high * 2 + 1;
+        }
+    }
+    // two more bits tell which quarter the final range covers
+    pending += 1;
+    output.push_with_pending(low >= QUARTER, &mut pending);
+    Ok(output.bytes)
+}
+
+pub fn decompress<Symbol: Copy + PartialEq>(
+    model: &impl PredictNextSymbol<Symbol>,
+    alphabet: &[Symbol],
+    compressed: &[u8],
+) -> Result<Vec<Symbol>, Error> {
+    if compressed.len() < HEADER_LENGTH {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "compressed data is shorter than its header",
+        ));
+    }
+    let symbol_count = u64::from_le_bytes(compressed[..HEADER_LENGTH].try_into().unwrap());
+    let mut input = BitInput {
+        bytes: &compressed[HEADER_LENGTH..],
+        bit_index: 0,
+    };
+    let mut low: u64 = 0;
+    let mut high: u64 = WHOLE - 1;
+    let mut value: u64 = 0;
+    for _ in 0..32 {
+        value = value * 2 + input.next_bit();
+    }
+    let mut data: Vec<Symbol> = Vec::new();
+    while (data.len() as u64) < symbol_count {
+        let frequencies = frequencies(model, alphabet, &data);
+        let total: u64 = frequencies.iter().sum();
+        let range = high - low + 1;
+        let scaled = ((value - low + 1) * total - 1) / range;
+        let mut from = 0;
+        let symbol_index = frequencies
+            .iter()
+            .position(|frequency| {
+                if scaled < from + frequency {
+                    true
+                } else {
+                    from += frequency;
+                    false
+                }
+            })
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "corrupt compressed data"))?;
+        let to = from + frequencies[symbol_index];
+        data.push(alphabet[symbol_index]);
+        high = low + range * to / total - 1;
+        low += range * from / total;
+        loop {
+            if high < HALF {
+                // the lower half, nothing to subtract
+            } else if low >= HALF {
+                low -= HALF;
+                high -= HALF;
+                value -= HALF;
+            } else if low >= QUARTER && high < HALF + QUARTER {
+                low -= QUARTER;
+                high -= QUARTER;
+                value -= QUARTER;
+            } else {
+                break;
+            }
+            low *= 2;
+            high = high * 2 + 1;
+            value = value * 2 + input.next_bit();
+        }
+    }
+    Ok(data)
+}
+
+pub fn bits_per_symbol(symbol_count: usize, compressed: &[u8]) -> f64 {
+    (compressed.len() * 8) as f64 / symbol_count as f64
+}
+
+// the normalized probability of each symbol of the alphabet as an integer frequency, at least 1
+// so that every symbol can be coded
+fn frequencies<Symbol: PartialEq>(
+    model: &impl PredictNextSymbol<Symbol>,
+    alphabet: &[Symbol],
+    preceding: &[Symbol],
+) -> Vec<u64> {
+    let distribution = model.next_symbol_distribution(preceding);
+    let scores: Vec<f64> = alphabet
+        .iter()
+        .map(|symbol| {
+            distribution
+                .iter()
+                .find(|(candidate, _)| candidate == symbol)
+                .map_or(0.0, |(_, score)| *score)
+        })
+        .map(|score| {
+            if score.is_finite() {
+                score.max(0.0)
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let total: f64 = scores.iter().sum();
+    scores
+        .into_iter()
+        .map(|score| {
+            if total > 0.0 {
+                ((score / total * FREQUENCY_SCALE) as u64).max(1)
+            } else {
+                1
+            }
+        })
+        .collect()
+}
+
+// where the symbol at symbol_index starts and ends among the cumulated frequencies, and their total
+fn cumulative_range(frequencies: &[u64], symbol_index: usize) -> (u64, u64, u64) {
+    let from: u64 = frequencies[..symbol_index].iter().sum();
+    let total: u64 = frequencies.iter().sum();
+    (from, from + frequencies[symbol_index], total)
+}
+
+// most significant bit first
+struct BitOutput {
+    bytes: Vec<u8>,
+    bit_count: usize,
+}
+
+impl BitOutput {
+    fn push(&mut self, bit: bool) {
+        let bit_in_byte = self.bit_count % 8;
+        if bit_in_byte == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> bit_in_byte;
+        }
+        self.bit_count += 1;
+    }
+    // bit followed by the opposite ones deferred while the range straddled the middle
+    fn push_with_pending(&mut self, bit: bool, pending: &mut u32) {
+        self.push(bit);
+        for _ in 0..*pending {
+            self.push(!bit);
+        }
+        *pending = 0;
+    }
+}
+
+// zeros past the end of bytes
+struct BitInput<'a> {
+    bytes: &'a [u8],
+    bit_index: usize,
+}
+
+impl BitInput<'_> {
+    fn next_bit(&mut self) -> u64 {
+        let bit = self
+            .bytes
+            .get(self.bit_index / 8)
+            .map_or(0, |byte| (byte >> (7 - self.bit_index % 8)) & 1);
+        self.bit_index += 1;
+        bit as u64
+    }
+}
@@ -1,19 +1,172 @@
+use serde::{Deserialize, Serialize};
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
 pub trait Evaluate<Value, Context> {
     fn evaluate(&self, context: Context) -> Value;
 }
 
-impl<Context, Term: Evaluate<bool, Context>, ContextIterator: Iterator<Item = Context>>
-    Evaluate<f64, ContextIterator> for Term
+// the fraction of contexts where the term is true, the ones where it is unknown are left out,
+// None when it is unknown in all of them
+impl<Context, Term: Evaluate<TruthValue, Context>, ContextIterator: Iterator<Item = Context>>
+    Evaluate<Option<f64>, ContextIterator> for Term
 {
-    fn evaluate(&self, context_iterator: ContextIterator) -> f64 {
+    fn evaluate(&self, context_iterator: ContextIterator) -> Option<f64> {
         let (trues, falses) = context_iterator.fold((0u32, 0u32), |(trues, falses), context| {
-            if self.evaluate(context) {
-                (trues + 1, falses)
-            } else {
-                (trues, falses + 1)
+            match self.evaluate(context) {
+                TruthValue::True => (trues + 1, falses),
+                TruthValue::False => (trues, falses + 1),
+                TruthValue::Unknown => (trues, falses),
             }
         });
-        trues as f64 / (trues + falses) as f64
+        (trues + falses > 0).then(|| trues as f64 / (trues + falses) as f64)
+    }
+}
+
+// Kleene's strong three-valued logic, Unknown when a term looks outside of the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TruthValue {
+    False,
+    Unknown,
+    True,
+}
+
+impl From<bool> for TruthValue {
+    fn from(value: bool) -> Self {
+        if value {
+            TruthValue::True
+        } else {
+            TruthValue::False
+        }
+    }
+}
+
+impl PartialEq<bool> for TruthValue {
+    fn eq(&self, other: &bool) -> bool {
+        *self == TruthValue::from(*other)
+    }
+}
+
+impl Not for TruthValue {
+    type Output = TruthValue;
+    fn not(self) -> TruthValue {
+        match self {
+            TruthValue::False => TruthValue::True,
+            TruthValue::Unknown => TruthValue::Unknown,
+            TruthValue::True => TruthValue::False,
+        }
+    }
+}
+
+// with False < Unknown < True, conjunction is the minimum and disjunction the maximum
+impl BitAnd for TruthValue {
+    type Output = TruthValue;
+    fn bitand(self, other: TruthValue) -> TruthValue {
+        self.min(other)
+    }
+}
+
+impl BitOr for TruthValue {
+    type Output = TruthValue;
+    fn bitor(self, other: TruthValue) -> TruthValue {
+        self.max(other)
+    }
+}
+
+// known only when both sides are known
+impl BitXor for TruthValue {
+    type Output = TruthValue;
+    fn bitxor(self, other: TruthValue) -> TruthValue {
+        match (self, other) {
+            (TruthValue::Unknown, _) | (_, TruthValue::Unknown) => TruthValue::Unknown,
+            (x, y) => (x != y).into(),
+        }
+    }
+}
+
+// true when every term is true, the empty conjunction is always true
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Conjunction<Term>(pub Vec<Term>);
+
+impl<Context: Clone, Term: Evaluate<TruthValue, Context>> Evaluate<TruthValue, Context>
+    for Conjunction<Term>
+{
+    fn evaluate(&self, context: Context) -> TruthValue {
+        self.0.iter().fold(TruthValue::True, |value, term| {
+            value & term.evaluate(context.clone())
+        })
+    }
+}
+
+// fires only when known to be true
+impl<Context: Clone, Term: Evaluate<TruthValue, Context>> Evaluate<bool, Context>
+    for Conjunction<Term>
+{
+    fn evaluate(&self, context: Context) -> bool {
+        Evaluate::<TruthValue, Context>::evaluate(self, context) == TruthValue::True
+    }
+}
+
+// the binary connectives depending on both operands, the others are constants or projections
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Connective {
+    // antecedent → consequent
+    #[default]
+    Implication,
+    // consequent → antecedent
+    Converse,
+    // antecedent ↔ consequent
+    Biconditional,
+    // antecedent ⊕ consequent
+    ExclusiveOr,
+    // antecedent ∧ ¬consequent
+    NonImplication,
+    // ¬antecedent ∧ consequent
+    ConverseNonImplication,
+    // antecedent ∧ consequent
+    And,
+    // antecedent ∨ consequent
+    Or,
+    // ¬(antecedent ∧ consequent)
+    Nand,
+    // ¬(antecedent ∨ consequent)
+    Nor,
+}
+
+impl Connective {
+    pub const ALL: [Connective; 10] = [
+        Connective::Implication,
+        Connective::Converse,
+        Connective::Biconditional,
+        Connective::ExclusiveOr,
+        Connective::NonImplication,
+        Connective::ConverseNonImplication,
+        Connective::And,
+        Connective::Or,
+        Connective::Nand,
+        Connective::Nor,
+    ];
+    pub fn apply(self, antecedent: bool, consequent: bool) -> bool {
+        match (self, antecedent, consequent) {
+            (Connective::Implication, a, c) => !a || c,
+            (Connective::Converse, a, c) => a || !c,
+            (Connective::Biconditional, a, c) => a == c,
+            (Connective::ExclusiveOr, a, c) => a != c,
+            (Connective::NonImplication, a, c) => a && !c,
+            (Connective::ConverseNonImplication, a, c) => !a && c,
+            (Connective::And, a, c) => a && c,
+            (Connective::Or, a, c) => a || c,
+            (Connective::Nand, a, c) => !(a && c),
+            (Connective::Nor, a, c) => !(a || c),
+        }
+    }
+    // what holding the connective says about the consequent when the antecedent is true:
+    // Some(true) that it is true, Some(false) that it is false, None nothing
+    pub fn consequent_when_antecedent(self) -> Option<bool> {
+        match (self.apply(true, true), self.apply(true, false)) {
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            _ => None,
+        }
     }
 }
 
@@ -1,6 +1,21 @@
-use super::sequence_prediction_model::SequencePredictionModel;
+use super::arithmetic_coding::{compress, decompress};
+use super::corpus::Corpus;
+use super::evaluation::{evaluate, EvaluationReport, PredictNextSymbol};
+use super::explanation::Explanation;
+use super::model_file::ModelFileFormat;
+use super::pruning::{Pruning, PruningReport};
+use super::sampling::{Random, Sampling};
+use super::sequence_prediction_model::{ConnectiveSelection, SequencePredictionModel};
+use super::surprisal::{surprising_spans, SurprisingSpan};
+use std::io::Error;
 
-#[derive(Debug)]
+// marks the characters to be filled in by infill
+pub const HOLE: char = '_';
+// around each document of a corpus
+pub const BEGIN_OF_DOCUMENT: char = '\u{2}';
+pub const END_OF_DOCUMENT: char = '\u{3}';
+
+#[derive(Debug, PartialEq)]
 pub struct CharacterSequencePredictionModel {
     sequence_prediction_model: SequencePredictionModel<char>,
 }
@@ -14,6 +29,88 @@ impl CharacterSequencePredictionModel {
             sequence_prediction_model: SequencePredictionModel::train(&input.into().0, window_size),
         }
     }
+    pub fn train_with_backoff<Input: Into<Data>>(
+        input: Input,
+        max_order: usize,
+    ) -> CharacterSequencePredictionModel {
+        CharacterSequencePredictionModel {
+            sequence_prediction_model: SequencePredictionModel::train_with_backoff(
+                &input.into().0,
+                max_order,
+            ),
+        }
+    }
+    pub fn train_bidirectional<Input: Into<Data>>(
+        input: Input,
+        window_size: usize,
+    ) -> CharacterSequencePredictionModel {
+        CharacterSequencePredictionModel {
+            sequence_prediction_model: SequencePredictionModel::train_bidirectional(
+                &input.into().0,
+                window_size,
+            ),
+        }
+    }
+    pub fn train_with_connectives<Input: Into<Data>>(
+        input: Input,
+        window_size: usize,
+        connective_selection: ConnectiveSelection,
+    ) -> CharacterSequencePredictionModel {
+        CharacterSequencePredictionModel {
+            sequence_prediction_model: SequencePredictionModel::train_with_connectives(
+                &input.into().0,
+                window_size,
+                connective_selection,
+            ),
+        }
+    }
+    pub fn train_pruned<Input: Into<Data>>(
+        input: Input,
+        window_size: usize,
+        pruning: &Pruning,
+    ) -> (CharacterSequencePredictionModel, PruningReport) {
+        let (sequence_prediction_model, report) =
+            SequencePredictionModel::train_pruned(&input.into().0, window_size, pruning);
+        (
+            CharacterSequencePredictionModel {
+                sequence_prediction_model,
+            },
+            report,
+        )
+    }
+    // removed rules are counted again from zero by later updates
+    pub fn prune(&mut self, pruning: &Pruning) -> PruningReport {
+        self.sequence_prediction_model.prune(pruning)
+    }
+    // no window spans two documents
+    pub fn train_documents<Input: Into<Data>>(
+        documents: impl IntoIterator<Item = Input>,
+        window_size: usize,
+    ) -> CharacterSequencePredictionModel {
+        let mut corpus = Corpus::new(BEGIN_OF_DOCUMENT, END_OF_DOCUMENT);
+        for document in documents {
+            corpus.push(document.into().0);
+        }
+        CharacterSequencePredictionModel {
+            sequence_prediction_model: SequencePredictionModel::train_corpus(&corpus, window_size),
+        }
+    }
+    pub fn add_document<Input: Into<Data>>(&mut self, input: Input) {
+        let corpus = Corpus::new(BEGIN_OF_DOCUMENT, END_OF_DOCUMENT);
+        self.sequence_prediction_model
+            .add_document(&corpus.bound(&input.into().0));
+    }
+    pub fn update<Input: Into<Data>>(&mut self, input: Input) {
+        self.sequence_prediction_model.update(&input.into().0);
+    }
+    pub fn save(&self, file_path: &str, format: ModelFileFormat) -> Result<(), Error> {
+        self.sequence_prediction_model.save(file_path, format)
+    }
+    pub fn load(file_path: &str, format: ModelFileFormat) -> Result<Self, Error> {
+        Ok(CharacterSequencePredictionModel {
+            sequence_prediction_model: SequencePredictionModel::load(file_path, format)?,
+        })
+    }
     pub fn predict<Input: Into<Data>>(&self, input: Input, length: usize) -> String {
         let mut sequence = input.into().0;
         let input_length = sequence.len();
@@ -21,6 +118,125 @@ impl CharacterSequencePredictionModel {
             .predict(&mut sequence, length);
         sequence.into_iter().skip(input_length).collect()
     }
+    // like predict, with the explanation of each predicted character
+    pub fn predict_explained<Input: Into<Data>>(
+        &self,
+        input: Input,
+        length: usize,
+    ) -> (String, Vec<Explanation<char>>) {
+        let mut sequence = input.into().0;
+        let input_length = sequence.len();
+        let explanations = self
+            .sequence_prediction_model
+            .predict_explained(&mut sequence, length);
+        (
+            sequence.into_iter().skip(input_length).collect(),
+            explanations,
+        )
+    }
+    // the input with every HOLE filled in, best with a bidirectionally trained model
+    pub fn infill<Input: Into<Data>>(&self, input: Input) -> String {
+        let mut sequence: Vec<Option<char>> = input
+            .into()
+            .0
+            .into_iter()
+            .map(|character| (character != HOLE).then_some(character))
+            .collect();
+        self.sequence_prediction_model.infill(&mut sequence);
+        sequence
+            .into_iter()
+            .map(|character| character.unwrap_or(HOLE))
+            .collect()
+    }
+    // continues input as the start of a document, until the end of it is predicted
+    pub fn predict_document<Input: Into<Data>>(&self, input: Input, max_length: usize) -> String {
+        let mut sequence: Vec<char> = [BEGIN_OF_DOCUMENT]
+            .into_iter()
+            .chain(input.into().0)
+            .collect();
+        let input_length = sequence.len();
+        self.sequence_prediction_model
+            .predict_until(&mut sequence, max_length, END_OF_DOCUMENT);
+        sequence.into_iter().skip(input_length).collect()
+    }
+    pub fn predict_distribution<Input: Into<Data>>(
+        &self,
+        input: Input,
+        minimum_probability: Option<f64>,
+    ) -> Vec<(char, f64)> {
+        self.sequence_prediction_model
+            .predict_distribution(&input.into().0, minimum_probability)
+    }
+    pub fn sample<Input: Into<Data>>(
+        &self,
+        input: Input,
+        length: usize,
+        sampling: &Sampling,
+        seed: u64,
+    ) -> String {
+        let mut sequence = input.into().0;
+        let input_length = sequence.len();
+        self.sequence_prediction_model.sample(
+            &mut sequence,
+            length,
+            sampling,
+            &mut Random::new(seed),
+        );
+        sequence.into_iter().skip(input_length).collect()
+    }
+    pub fn predict_beam<Input: Into<Data>>(
+        &self,
+        input: Input,
+        length: usize,
+        beam_width: usize,
+        count: usize,
+    ) -> Vec<(String, f64)> {
+        self.sequence_prediction_model
+            .predict_beam(&input.into().0, length, beam_width, count)
+            .into_iter()
+            .map(|(continuation, score)| (continuation.into_iter().collect(), score))
+            .collect()
+    }
+    // how unexpected each character of the input is, in bits
+    pub fn surprisal<Input: Into<Data>>(&self, input: Input) -> Vec<f64> {
+        self.sequence_prediction_model.surprisal(&input.into().0)
+    }
+    // the runs of characters more surprising than threshold bits, start and end count characters
+    pub fn surprising_spans<Input: Into<Data>>(
+        &self,
+        input: Input,
+        threshold: f64,
+    ) -> Vec<SurprisingSpan> {
+        surprising_spans(&self.surprisal(input), threshold)
+    }
+    pub fn evaluate<Input: Into<Data>>(&self, test_input: Input, top_k: usize) -> EvaluationReport {
+        evaluate(self, &test_input.into().0, top_k)
+    }
+    // arithmetic coded with the distributions of the model, every character of the input must
+    // have been seen in training
+    pub fn compress<Input: Into<Data>>(&self, input: Input) -> Result<Vec<u8>, Error> {
+        compress(
+            self,
+            self.sequence_prediction_model.alphabet(),
+            &input.into().0,
+        )
+    }
+    pub fn decompress(&self, compressed: &[u8]) -> Result<String, Error> {
+        Ok(
+            decompress(self, self.sequence_prediction_model.alphabet(), compressed)?
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+impl PredictNextSymbol<char> for CharacterSequencePredictionModel {
+    fn next_symbol_distribution(&self, preceding: &[char]) -> Vec<(char, f64)> {
+        let window_size = self.sequence_prediction_model.window_size();
+        let context = &preceding[preceding.len().saturating_sub(window_size - 1)..];
+        self.sequence_prediction_model
+            .predict_distribution(context, None)
+    }
 }
 
 pub struct Data(Vec<char>);
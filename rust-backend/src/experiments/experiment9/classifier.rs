@@ -0,0 +1,206 @@
+use super::character_sequence_prediction_model::{
+    CharacterSequencePredictionModel, BEGIN_OF_DOCUMENT, END_OF_DOCUMENT,
+};
+use super::corpus::Corpus;
+use super::evaluation::split_train_test;
+use std::{fmt, fs::read_to_string, io::Error};
+
+// one model per label, text is given the label of the model that predicts it best
+#[derive(Debug, PartialEq)]
+pub struct Classifier {
+    models: Vec<(String, CharacterSequencePredictionModel)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelScore {
+    pub label: String,
+    // log2 P(text | label) of text as a whole document, its end included
+    pub log_likelihood: f64,
+    // P(label | text), with every label equally likely beforehand
+    pub confidence: f64,
+}
+
+// counts[actual][predicted], in the order of labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfusionMatrix {
+    pub labels: Vec<String>,
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl Classifier {
+    // every text is a document of the model of its label, labels keep their first order
+    pub fn train<'a>(
+        labelled_texts: impl IntoIterator<Item = (&'a str, &'a str)>,
+        window_size: usize,
+    ) -> Classifier {
+        let mut texts_by_label: Vec<(String, Vec<&str>)> = Vec::new();
+        for (label, text) in labelled_texts {
+            match texts_by_label.iter_mut().find(|(other, _)| other == label) {
+                Some((_, texts)) => texts.push(text),
+                None => texts_by_label.push((label.to_string(), vec![text])),
+            }
+        }
+        Classifier {
+            models: texts_by_label
+                .into_iter()
+                .map(|(label, texts)| {
+                    let model =
+                        CharacterSequencePredictionModel::train_documents(texts, window_size);
+                    (label, model)
+                })
+                .collect(),
+        }
+    }
+    // trains on all but the last test_fraction of each file, the held-out ends are cut into
+    // samples of sample_length characters to build the confusion matrix
+    pub fn train_files(
+        labelled_file_paths: &[(&str, &str)],
+        window_size: usize,
+        test_fraction: f64,
+        sample_length: usize,
+    ) -> Result<(Classifier, ConfusionMatrix), Error> {
+        let mut train_texts: Vec<(&str, String)> = Vec::new();
+        let mut test_samples: Vec<(&str, String)> = Vec::new();
+        for (label, file_path) in labelled_file_paths {
+            let data: Vec<char> = read_to_string(file_path)?.chars().collect();
+            let (train_data, test_data) = split_train_test(&data, test_fraction);
+            train_texts.push((label, train_data.iter().collect()));
+            for sample in test_data.chunks(sample_length.max(1)) {
+                test_samples.push((label, sample.iter().collect()));
+            }
+        }
+        let classifier = Classifier::train(
+            train_texts
+                .iter()
+                .map(|(label, text)| (*label, text.as_str())),
+            window_size,
+        );
+        let confusion_matrix = classifier.confusion_matrix(
+            test_samples
+                .iter()
+                .map(|(label, text)| (*label, text.as_str())),
+        );
+        Ok((classifier, confusion_matrix))
+    }
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.models.iter().map(|(label, _)| label.as_str())
+    }
+    // every label with its score, most likely first, text is scored between the begin and
+    // end of document symbols like the documents the models were trained on
+    pub fn classify(&self, text: &str) -> Vec<LabelScore> {
+        let corpus = Corpus::new(BEGIN_OF_DOCUMENT, END_OF_DOCUMENT);
+        let document: String = corpus
+            .bound(&text.chars().collect::<Vec<char>>())
+            .into_iter()
+            .collect();
+        let log_likelihoods: Vec<f64> = self
+            .models
+            .iter()
+            .map(|(_, model)| {
+                let report = model.evaluate(document.as_str(), 1);
+                if report.predictions == 0 {
+                    0.0
+                } else {
+                    -report.cross_entropy * report.predictions as f64
+                }
+            })
+            .collect();
+        // relative to the best one, so that long texts do not underflow
+        let best = log_likelihoods
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let total: f64 = log_likelihoods
+            .iter()
+            .map(|log_likelihood| (log_likelihood - best).exp2())
+            .sum();
+        let mut scores: Vec<LabelScore> = self
+            .models
+            .iter()
+            .zip(log_likelihoods)
+            .map(|((label, _), log_likelihood)| LabelScore {
+                label: label.clone(),
+                log_likelihood,
+                confidence: (log_likelihood - best).exp2() / total,
+            })
+            .collect();
+        scores.sort_by(|a, b| b.log_likelihood.partial_cmp(&a.log_likelihood).unwrap());
+        scores
+    }
+    // labels the classifier does not know are added as rows nothing is predicted for, a
+    // classifier without labels predicts nothing and counts no text
+    pub fn confusion_matrix<'a>(
+        &self,
+        labelled_texts: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> ConfusionMatrix {
+        let mut labels: Vec<String> = self.labels().map(str::to_string).collect();
+        let mut counts = vec![vec![0; labels.len()]; labels.len()];
+        for (actual, text) in labelled_texts {
+            let actual = match labels.iter().position(|label| label == actual) {
+                Some(actual) => actual,
+                None => {
+                    labels.push(actual.to_string());
+                    for row in counts.iter_mut() {
+                        row.push(0);
+                    }
+                    counts.push(vec![0; labels.len()]);
+                    labels.len() - 1
+                }
+            };
+            if let Some(score) = self.classify(text).first() {
+                let predicted = labels
+                    .iter()
+                    .position(|label| *label == score.label)
+                    .unwrap();
+                counts[actual][predicted] += 1;
+            }
+        }
+        ConfusionMatrix { labels, counts }
+    }
+}
+
+impl ConfusionMatrix {
+    // the fraction of samples on the diagonal, 0 without samples
+    pub fn accuracy(&self) -> f64 {
+        let correct: usize = (0..self.labels.len())
+            .map(|index| self.counts[index][index])
+            .sum();
+        let total: usize = self.counts.iter().flatten().sum();
+        if total == 0 {
+            0.0
+        } else {
+            correct as f64 / total as f64
+        }
+    }
+}
+
+// one row per actual label, one column per predicted label
+impl fmt::Display for ConfusionMatrix {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .labels
+            .iter()
+            .map(|label| label.chars().count())
+            .chain(
+                self.counts
+                    .iter()
+                    .flatten()
+                    .map(|count| count.to_string().len()),
+            )
+            .max()
+            .unwrap_or(0);
+        write!(formatter, "{:width$}", "")?;
+        for label in &self.labels {
+            write!(formatter, " {label:>width$}")?;
+        }
+        writeln!(formatter)?;
+        for (label, row) in self.labels.iter().zip(&self.counts) {
+            write!(formatter, "{label:width$}")?;
+            for count in row {
+                write!(formatter, " {count:>width$}")?;
+            }
+            writeln!(formatter)?;
+        }
+        write!(formatter, "accuracy: {:.3}", self.accuracy())
+    }
+}
@@ -0,0 +1,32 @@
+// documents trained on one at a time, so that no window spans two of them,
+// each between a begin and an end symbol that the model learns like any other
+#[derive(Debug, Clone, PartialEq)]
+pub struct Corpus<Symbol> {
+    begin: Symbol,
+    end: Symbol,
+    documents: Vec<Vec<Symbol>>,
+}
+
+impl<Symbol: Copy> Corpus<Symbol> {
+    pub fn new(begin: Symbol, end: Symbol) -> Corpus<Symbol> {
+        Corpus {
+            begin,
+            end,
+            documents: Vec::new(),
+        }
+    }
+    pub fn push(&mut self, document: Vec<Symbol>) {
+        self.documents.push(document);
+    }
+    // every document between the begin and the end symbol
+    pub fn bounded_documents(&self) -> impl Iterator<Item = Vec<Symbol>> + '_ {
+        self.documents.iter().map(|document| self.bound(document))
+    }
+    pub fn bound(&self, document: &[Symbol]) -> Vec<Symbol> {
+        [self.begin]
+            .into_iter()
+            .chain(document.iter().copied())
+            .chain([self.end])
+            .collect()
+    }
+}
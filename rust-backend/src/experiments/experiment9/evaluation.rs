@@ -0,0 +1,71 @@
+pub trait PredictNextSymbol<Symbol> {
+    // candidate next symbols with their scores, given the symbols preceding them
+    fn next_symbol_distribution(&self, preceding: &[Symbol]) -> Vec<(Symbol, f64)>;
+}
+
+// a symbol the model gives no probability to is charged as if it had this one,
+// otherwise a single miss would make cross-entropy infinite
+pub const MINIMUM_PROBABILITY: f64 = 1e-6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvaluationReport {
+    pub predictions: usize,
+    pub top_1_accuracy: f64,
+    pub top_k: usize,
+    pub top_k_accuracy: f64,
+    // bits per symbol
+    pub cross_entropy: f64,
+    pub perplexity: f64,
+}
+
+// predicts every symbol of test_data from the ones preceding it (the first one has none),
+// with fewer than two symbols there is nothing to predict and every rate is 0
+pub fn evaluate<Symbol: PartialEq>(
+    model: &impl PredictNextSymbol<Symbol>,
+    test_data: &[Symbol],
+    top_k: usize,
+) -> EvaluationReport {
+    let mut top_1_hits = 0;
+    let mut top_k_hits = 0;
+    let mut bits = 0.0;
+    let predictions = test_data.len().saturating_sub(1);
+    for index in 1..test_data.len() {
+        let actual = &test_data[index];
+        let mut distribution = model.next_symbol_distribution(&test_data[..index]);
+        distribution.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        let total: f64 = distribution.iter().map(|(_, score)| score).sum();
+        let rank = distribution
+            .iter()
+            .position(|(symbol, score)| symbol == actual && *score > 0.0);
+        if rank == Some(0) {
+            top_1_hits += 1;
+        }
+        if rank.is_some_and(|rank| rank < top_k) {
+            top_k_hits += 1;
+        }
+        let probability = rank.map_or(0.0, |rank| distribution[rank].1 / total);
+        bits -= probability.max(MINIMUM_PROBABILITY).log2();
+    }
+    let rate = |count: f64| {
+        if predictions > 0 {
+            count / predictions as f64
+        } else {
+            0.0
+        }
+    };
+    let cross_entropy = rate(bits);
+    EvaluationReport {
+        predictions,
+        top_1_accuracy: rate(top_1_hits as f64),
+        top_k,
+        top_k_accuracy: rate(top_k_hits as f64),
+        cross_entropy,
+        perplexity: cross_entropy.exp2(),
+    }
+}
+
+// the last test_fraction of the data is held out, always the same for the same data
+pub fn split_train_test<Symbol>(data: &[Symbol], test_fraction: f64) -> (&[Symbol], &[Symbol]) {
+    let test_length = (data.len() as f64 * test_fraction.clamp(0.0, 1.0)).round() as usize;
+    data.split_at(data.len() - test_length)
+}
@@ -0,0 +1,40 @@
+use super::boolean_algebra::Connective;
+use serde::{Deserialize, Serialize};
+
+// why a symbol was predicted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explanation<Symbol> {
+    pub prediction: Symbol,
+    // every symbol of the alphabet with its final score, best first
+    pub candidates: Vec<CandidateScore<Symbol>>,
+    // the rules looked at, in rule order
+    pub rules: Vec<ExplainedRule<Symbol>>,
+    // the position in rules of the one that decided the prediction, when a single one did
+    pub deciding_rule: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CandidateScore<Symbol> {
+    pub symbol: Symbol,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplainedRule<Symbol> {
+    // all of them must hold for the rule to fire
+    pub antecedent: Vec<ExplainedTerm<Symbol>>,
+    pub consequent: Symbol,
+    pub antecedent_count: u32,
+    pub rule_count: u32,
+    pub confidence: f64,
+    // what a firing rule says about its consequent
+    pub connective: Connective,
+    pub fired: bool,
+}
+
+// the symbol at relative_index from the predicted position
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplainedTerm<Symbol> {
+    pub relative_index: i32,
+    pub symbol: Symbol,
+}
@@ -1,16 +1,41 @@
-mod boolean_algebra;
-mod character_sequence_prediction_model;
-mod sequence_prediction_model;
-
-pub fn main() {
-    // here only to avoid "unused code" warning
-    character_sequence_prediction_model::CharacterSequencePredictionModel::train("", 0)
-        .predict("", 0);
-}
+pub mod arithmetic_coding;
+pub mod boolean_algebra;
+pub mod character_sequence_prediction_model;
+pub mod classifier;
+pub mod corpus;
+pub mod evaluation;
+pub mod explanation;
+pub mod model_file;
+pub mod pruning;
+pub mod sampling;
+pub mod sequence_prediction_model;
+pub mod surprisal;
+pub mod tokenizer;
+pub mod word_sequence_prediction_model;
 
 mod test {
+    #[cfg(test)]
+    use super::arithmetic_coding::bits_per_symbol;
+    #[cfg(test)]
+    use super::boolean_algebra::Connective;
     #[cfg(test)]
     use super::character_sequence_prediction_model::CharacterSequencePredictionModel;
+    #[cfg(test)]
+    use super::classifier::{Classifier, ConfusionMatrix};
+    #[cfg(test)]
+    use super::evaluation::split_train_test;
+    #[cfg(test)]
+    use super::model_file::{corpus_hash, ModelFileFormat, FORMAT_VERSION};
+    #[cfg(test)]
+    use super::pruning::{CountMinSketch, Pruning, PruningReport};
+    #[cfg(test)]
+    use super::sampling::Sampling;
+    #[cfg(test)]
+    use super::sequence_prediction_model::ConnectiveSelection;
+    #[cfg(test)]
+    use super::tokenizer::{pre_tokenize, Tokenizer, TokenizerKind, UNKNOWN_TOKEN};
+    #[cfg(test)]
+    use super::word_sequence_prediction_model::WordSequencePredictionModel;
 
     #[test]
     fn predict_1() {
@@ -37,13 +62,627 @@ mod test {
         assert_eq!(model.predict("c", 10), "abcabcabca");
     }
 
-    // failed to predict with this experiment strategy, " " <-> " " has value 0.7
+    // "ma" is followed both by "m" and " ", the three previous characters tell them apart
     #[test]
     fn predict_3() {
-        let model = CharacterSequencePredictionModel::train("mamma mamma mamma mamma", 2);
-        dbg!(&model);
+        let model = CharacterSequencePredictionModel::train("mamma mamma mamma mamma", 4);
+        assert_eq!(model.predict(" ", 6), "mamma ");
+        assert_eq!(model.predict(" ma", 6), "mma ma");
+        assert_eq!(model.predict("mm", 6), "a mamm");
+    }
+
+    #[test]
+    #[should_panic(expected = "rules can look at 16 symbols at most")]
+    fn window_too_large() {
+        CharacterSequencePredictionModel::train_bidirectional("mamma mamma mamma mamma", 10);
+    }
+
+    #[test]
+    fn predict_with_backoff() {
+        let model =
+            CharacterSequencePredictionModel::train_with_backoff("mamma mamma mamma mamma", 5);
         assert_eq!(model.predict(" ", 6), "mamma ");
         assert_eq!(model.predict(" ma", 6), "mma ma");
         assert_eq!(model.predict("mm", 6), "a mamm");
     }
+
+    #[test]
+    fn predict_with_backoff_of_order_0() {
+        let model = CharacterSequencePredictionModel::train_with_backoff("abbabb", 0);
+        assert_eq!(model.predict("a", 3), "bbb");
+        let model = CharacterSequencePredictionModel::train_with_backoff("", 2);
+        assert_eq!(model.predict("ab", 3), "");
+    }
+
+    #[test]
+    fn predict_with_backoff_from_unseen_context() {
+        let model = CharacterSequencePredictionModel::train_with_backoff("abcabcabcabc", 4);
+        assert_eq!(model.predict("cbab", 1), "c");
+        assert_eq!(model.predict("zzzb", 5), "cabca");
+    }
+
+    #[test]
+    fn predict_beam() {
+        let model = CharacterSequencePredictionModel::train("mamma mamma mamma mamma", 4);
+        let beams = model.predict_beam(" ", 6, 3, 2);
+        assert_eq!(beams.len(), 2);
+        assert_eq!(beams[0].0, "mamma ");
+        assert!(beams[0].1 >= beams[1].1);
+        let beams = model.predict_beam("mm", 6, 1, 1);
+        assert_eq!(beams[0].0, model.predict("mm", 6));
+    }
+
+    #[test]
+    fn sample() {
+        let model =
+            CharacterSequencePredictionModel::train_with_backoff("mamma mamma mamma mamma", 3);
+        let sampling = Sampling {
+            temperature: 0.8,
+            top_k: Some(3),
+            top_p: Some(0.9),
+        };
+        let sampled = model.sample("m", 30, &sampling, 42);
+        assert_eq!(sampled.chars().count(), 30);
+        assert_eq!(sampled, model.sample("m", 30, &sampling, 42));
+        let argmax = Sampling {
+            temperature: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(model.sample(" ", 6, &argmax, 7), model.predict(" ", 6));
+        let greedy = Sampling {
+            top_k: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(model.sample(" ", 6, &greedy, 7), model.predict(" ", 6));
+    }
+
+    #[test]
+    fn predict_distribution() {
+        let model = CharacterSequencePredictionModel::train("mamma mamma mamma mamma", 4);
+        let distribution = model.predict_distribution("m", None);
+        assert_eq!(distribution.len(), 3);
+        assert_eq!(distribution[0].0, 'a');
+        assert!((distribution.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(distribution.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        let cut = model.predict_distribution("m", Some(distribution[1].1));
+        assert_eq!(cut, distribution[..2]);
+        let model = CharacterSequencePredictionModel::train_with_backoff("abcabcabcabc", 3);
+        let distribution = model.predict_distribution("ab", Some(0.01));
+        assert_eq!(distribution[0].0, 'c');
+        assert!(distribution[0].1 > 0.5);
+    }
+
+    // after "c" both "a" and "b" follow half of the time, "b" is better supported by the rule
+    // without antecedent, "x" and "y" are tied in everything and "x" comes first in the data
+    #[test]
+    fn predict_distribution_breaks_ties_like_predict() {
+        let model = CharacterSequencePredictionModel::train("cacbbb", 2);
+        assert_eq!(model.predict("c", 1), "b");
+        let distribution = model.predict_distribution("c", None);
+        assert_eq!(distribution[0].0, 'b');
+        assert_eq!(distribution[1].0, 'a');
+        assert_eq!(distribution[0].1, distribution[1].1);
+        let model = CharacterSequencePredictionModel::train("zxzyww", 2);
+        assert_eq!(model.predict("z", 1), "x");
+        assert_eq!(model.predict_distribution("z", None)[0].0, 'x');
+    }
+
+    #[test]
+    fn save_and_load() {
+        let model = CharacterSequencePredictionModel::train("mamma mamma mamma mamma", 4);
+        for (format, extension) in [
+            (ModelFileFormat::Ron, "ron"),
+            (ModelFileFormat::Json, "json"),
+        ] {
+            let path = std::env::temp_dir().join(format!("experiment9-save-and-load.{extension}"));
+            let path = path.to_str().unwrap();
+            model.save(path, format).unwrap();
+            let loaded = CharacterSequencePredictionModel::load(path, format).unwrap();
+            assert_eq!(loaded, model);
+            assert_eq!(loaded.predict(" ", 6), "mamma ");
+        }
+    }
+
+    // FNV-1a of the little-endian code points, whatever the platform
+    #[test]
+    fn corpus_hash_is_portable() {
+        assert_eq!(corpus_hash(&['a', 'b']), 0xa71bed7b7ba66346);
+    }
+
+    #[test]
+    fn load_rejects_other_format_versions() {
+        let model = CharacterSequencePredictionModel::train_with_backoff("abcabc", 2);
+        let path = std::env::temp_dir().join("experiment9-other-format-version.json");
+        let path = path.to_str().unwrap();
+        model.save(path, ModelFileFormat::Json).unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::write(
+            path,
+            text.replace(
+                &format!("\"format_version\":{FORMAT_VERSION}"),
+                "\"format_version\":0",
+            ),
+        )
+        .unwrap();
+        let error =
+            CharacterSequencePredictionModel::load(path, ModelFileFormat::Json).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unsupported model file format version 0"));
+    }
+
+    #[test]
+    fn update_equals_training_on_concatenated_data() {
+        let mut model = CharacterSequencePredictionModel::train("mamma mam", 4);
+        model.update("ma mamma");
+        model.update(" papà");
+        assert_eq!(
+            model,
+            CharacterSequencePredictionModel::train("mamma mamma mamma papà", 4)
+        );
+        let mut model = CharacterSequencePredictionModel::train_with_backoff("ab", 3);
+        model.update("");
+        model.update("cabcab");
+        assert_eq!(
+            model,
+            CharacterSequencePredictionModel::train_with_backoff("abcabcab", 3)
+        );
+        assert_eq!(model.predict("a", 5), "bcabc");
+    }
+
+    #[test]
+    fn update_loaded_model() {
+        let path = std::env::temp_dir().join("experiment9-update-loaded-model.ron");
+        let path = path.to_str().unwrap();
+        CharacterSequencePredictionModel::train("abcab", 3)
+            .save(path, ModelFileFormat::Ron)
+            .unwrap();
+        let mut model = CharacterSequencePredictionModel::load(path, ModelFileFormat::Ron).unwrap();
+        model.update("cabcd");
+        assert_eq!(
+            model,
+            CharacterSequencePredictionModel::train("abcabcabcd", 3)
+        );
+    }
+
+    #[test]
+    fn evaluate() {
+        let data: Vec<char> = "abcabcabcabcabcabcabcabcabcabc".chars().collect();
+        let (train_data, test_data) = split_train_test(&data, 0.2);
+        assert_eq!((train_data.len(), test_data.len()), (24, 6));
+        assert_eq!(split_train_test(&data, 0.2), (train_data, test_data));
+        let train_text: String = train_data.iter().collect();
+        let test_text: String = test_data.iter().collect();
+        let model = CharacterSequencePredictionModel::train(train_text.as_str(), 2);
+        let report = model.evaluate(test_text.as_str(), 2);
+        assert_eq!(report.predictions, 5);
+        assert_eq!(report.top_1_accuracy, 1.0);
+        assert_eq!(report.top_k_accuracy, 1.0);
+        assert!(report.cross_entropy < 1.0);
+        assert!((report.perplexity - report.cross_entropy.exp2()).abs() < 1e-9);
+        let reversed = model.evaluate("acbacb", 2);
+        assert_eq!(reversed.top_1_accuracy, 0.0);
+        assert!(reversed.cross_entropy > report.cross_entropy);
+        let model = CharacterSequencePredictionModel::train_with_backoff(train_text.as_str(), 2);
+        let report = model.evaluate("acbacb", 3);
+        assert_eq!(report.top_k_accuracy, 1.0);
+        assert!(report.cross_entropy.is_finite() && report.cross_entropy > 1.0);
+        for too_short in ["", "a"] {
+            let report = model.evaluate(too_short, 3);
+            assert_eq!(report.predictions, 0);
+            assert_eq!(report.top_1_accuracy, 0.0);
+            assert_eq!(report.top_k_accuracy, 0.0);
+            assert_eq!(report.cross_entropy, 0.0);
+            assert_eq!(report.perplexity, 1.0);
+        }
+    }
+
+    #[test]
+    fn pre_tokenize_italian() {
+        assert_eq!(
+            pre_tokenize("l'acqua dell’anno, un po' fredda."),
+            vec!["l'", "acqua", " dell’", "anno", ",", " un", " po'", " fredda", "."]
+        );
+    }
+
+    #[test]
+    fn word_tokenizer() {
+        let text = "l'acqua è fresca. l'acqua è buona.";
+        let tokenizer = Tokenizer::words(text, 2);
+        assert_eq!(
+            tokenizer.decode(&tokenizer.encode(text)),
+            "l'acqua è <unk>. l'acqua è <unk>."
+        );
+        assert_eq!(tokenizer.encode(" vino")[1], UNKNOWN_TOKEN);
+        let tokenizer = Tokenizer::words(text, 1);
+        assert_eq!(tokenizer.decode(&tokenizer.encode(text)), text);
+    }
+
+    #[test]
+    fn byte_pair_tokenizer() {
+        let text = "la mamma ama la mela, la mamma ama la mamma";
+        let tokenizer = Tokenizer::byte_pairs(text, 8);
+        let TokenizerKind::BytePairs { merges } = tokenizer.kind() else {
+            panic!("not a byte pair tokenizer");
+        };
+        assert_eq!(merges.len(), 8);
+        let tokens = tokenizer.encode(text);
+        assert!(tokens.len() < text.chars().count() / 2);
+        assert_eq!(tokenizer.decode(&tokens), text);
+        assert_eq!(tokenizer.decode(&tokenizer.encode("la lama")), "la lama");
+        assert_eq!(tokenizer.encode("x"), vec![UNKNOWN_TOKEN]);
+    }
+
+    #[test]
+    fn predict_words() {
+        let text = "la mamma ama la mela. la mamma ama la mela. la mamma ama la mela.";
+        let model = WordSequencePredictionModel::train(Tokenizer::words(text, 1), text, 3);
+        assert_eq!(model.predict("la mamma", 3), " ama la mela");
+        assert_eq!(model.predict_words("la mamma", 2), vec![" ama", " la"]);
+        assert_eq!(
+            model.predict_distribution("la mamma ama la", None)[0].0,
+            " mela"
+        );
+        let path = std::env::temp_dir().join("experiment9-predict-words.json");
+        let path = path.to_str().unwrap();
+        model.save(path, ModelFileFormat::Json).unwrap();
+        let loaded = WordSequencePredictionModel::load(path, ModelFileFormat::Json).unwrap();
+        assert_eq!(loaded, model);
+        assert_eq!(loaded.predict("mela.", 2), " la mamma");
+    }
+
+    #[test]
+    fn infill() {
+        let model =
+            CharacterSequencePredictionModel::train_bidirectional("mamma mamma mamma mamma", 3);
+        assert_eq!(model.infill("ma_ma"), "mamma");
+        assert_eq!(model.infill("_amma"), "mamma");
+        assert_eq!(model.infill("mamm_ m_mma"), "mamma mamma");
+        assert_eq!(model.infill("ma__a"), "mamma");
+        assert_eq!(model.infill("mamma"), "mamma");
+        let model = CharacterSequencePredictionModel::train_bidirectional("abcdabcdabcd", 2);
+        assert_eq!(model.infill("ab_d"), "abcd");
+        assert_eq!(model.infill("_b_d"), "abcd");
+    }
+
+    #[test]
+    fn predict_explained() {
+        let model = CharacterSequencePredictionModel::train("mamma mamma mamma mamma", 4);
+        let (prediction, explanations) = model.predict_explained(" ma", 3);
+        assert_eq!(prediction, model.predict(" ma", 3));
+        assert_eq!(explanations.len(), 3);
+        let explanation = &explanations[0];
+        assert_eq!(explanation.prediction, 'm');
+        assert_eq!(explanation.candidates[0].symbol, 'm');
+        let deciding_rule = &explanation.rules[explanation.deciding_rule.unwrap()];
+        assert!(deciding_rule.fired);
+        assert_eq!(deciding_rule.consequent, 'm');
+        assert_eq!(deciding_rule.confidence, 1.0);
+        assert!(explanation.rules.iter().any(|rule| !rule.fired));
+        let json = serde_json::to_string(&explanations).unwrap();
+        assert!(json.contains("\"deciding_rule\""));
+        let model = CharacterSequencePredictionModel::train_with_backoff("abcabcabcabc", 2);
+        let (prediction, explanations) = model.predict_explained("ab", 2);
+        assert_eq!(prediction, "ca");
+        assert_eq!(explanations[0].deciding_rule, None);
+        assert!(explanations[0].rules.iter().all(|rule| rule.fired));
+    }
+
+    #[test]
+    fn documents() {
+        let model = CharacterSequencePredictionModel::train_documents(["abc", "abc", "xyz"], 2);
+        // trained on "abcabcxyz" as a whole, "c" would be followed by "a" or "x"
+        assert_eq!(model.predict_document("", 10), "abc");
+        assert_eq!(model.predict_document("x", 10), "yz");
+        assert_eq!(model.predict("c", 1), "\u{3}");
+        let mut model = CharacterSequencePredictionModel::train_documents(["abc"], 2);
+        model.add_document("abc");
+        model.add_document("xyz");
+        assert_eq!(
+            model,
+            CharacterSequencePredictionModel::train_documents(["abc", "abc", "xyz"], 2)
+        );
+        let mut model = CharacterSequencePredictionModel::train_bidirectional("", 3);
+        model.add_document("mamma");
+        model.add_document("papà");
+        assert_eq!(model.infill("ma_ma"), "mamma");
+    }
+
+    #[test]
+    fn train_pruned() {
+        let text = "mamma mamma mamma mamma papà";
+        let (model, report) =
+            CharacterSequencePredictionModel::train_pruned(text, 3, &Default::default());
+        assert_eq!(model, CharacterSequencePredictionModel::train(text, 3));
+        assert_eq!(
+            (
+                report.removed_by_support,
+                report.removed_by_confidence,
+                report.removed_by_utility
+            ),
+            (0, 0, 0)
+        );
+        let pruning = Pruning {
+            minimum_support: Some(3),
+            minimum_confidence: Some(0.5),
+            minimum_utility: Some(4.0),
+        };
+        let (pruned, report) = CharacterSequencePredictionModel::train_pruned(text, 3, &pruning);
+        assert!(report.removed_by_confidence > 0);
+        assert!(report.removed_by_utility > 0);
+        let mut model = CharacterSequencePredictionModel::train(text, 3);
+        let report_after_training = model.prune(&pruning);
+        assert_eq!(model, pruned);
+        // the rules under minimum support were left out while counting, the rest as after it
+        assert!(report_after_training.removed_by_support > report.removed_by_support);
+        assert_eq!(
+            report,
+            PruningReport {
+                removed_by_support: report.removed_by_support,
+                ..report_after_training
+            }
+        );
+        assert_eq!(
+            model.prune(&pruning),
+            PruningReport {
+                kept: report.kept,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn count_min_sketch_never_underestimates() {
+        let mut sketch = CountMinSketch::new(4);
+        let items = ["ma", "mm", "am", "a ", " p", "pa", "ap", "pà"];
+        for (count, item) in items.iter().enumerate() {
+            for _ in 0..count {
+                sketch.add(item);
+            }
+        }
+        for (count, item) in items.iter().enumerate() {
+            assert!(sketch.estimate(item) >= count as u32);
+        }
+        let mut wide = CountMinSketch::new(1 << 10);
+        wide.add(&"ma");
+        wide.add(&"ma");
+        assert_eq!((wide.estimate(&"ma"), wide.estimate(&"pa")), (2, 0));
+    }
+
+    // the symbols of the pruned rules are left to the shorter contexts
+    #[test]
+    fn backoff_distribution_of_pruned_model() {
+        let mut model =
+            CharacterSequencePredictionModel::train_with_backoff("mamma mamma mamma mamma papà", 2);
+        let report = model.prune(&Pruning {
+            minimum_support: None,
+            minimum_confidence: Some(0.3),
+            minimum_utility: None,
+        });
+        assert!(report.removed_by_confidence > 0);
+        for context in ["", "m", "ma", "pa", "à"] {
+            let distribution = model.predict_distribution(context, None);
+            assert!((distribution.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn connective_truth_tables() {
+        let table = |connective: Connective| {
+            [(true, true), (true, false), (false, true), (false, false)]
+                .map(|(antecedent, consequent)| connective.apply(antecedent, consequent))
+        };
+        assert_eq!(table(Connective::Implication), [true, false, true, true]);
+        assert_eq!(table(Connective::Converse), [true, true, false, true]);
+        assert_eq!(table(Connective::Biconditional), [true, false, false, true]);
+        assert_eq!(table(Connective::ExclusiveOr), [false, true, true, false]);
+        assert_eq!(
+            table(Connective::NonImplication),
+            [false, true, false, false]
+        );
+        assert_eq!(
+            table(Connective::ConverseNonImplication),
+            [false, false, true, false]
+        );
+        assert_eq!(table(Connective::And), [true, false, false, false]);
+        assert_eq!(table(Connective::Or), [true, true, true, false]);
+        assert_eq!(table(Connective::Nand), [false, true, true, true]);
+        assert_eq!(table(Connective::Nor), [false, false, false, true]);
+        assert_eq!(
+            Connective::Implication.consequent_when_antecedent(),
+            Some(true)
+        );
+        assert_eq!(
+            Connective::ExclusiveOr.consequent_when_antecedent(),
+            Some(false)
+        );
+        assert_eq!(Connective::Converse.consequent_when_antecedent(), None);
+    }
+
+    #[test]
+    fn connectives() {
+        let text = "abababababab";
+        assert_eq!(
+            CharacterSequencePredictionModel::train_with_connectives(
+                text,
+                2,
+                ConnectiveSelection::Fixed(Connective::Implication)
+            ),
+            CharacterSequencePredictionModel::train(text, 2)
+        );
+        let model = CharacterSequencePredictionModel::train_with_connectives(
+            "aababababab",
+            2,
+            ConnectiveSelection::BestPerRule,
+        );
+        assert_eq!(model.predict("ab", 4), "abab");
+        let (_, explanations) = model.predict_explained("a", 1);
+        let connective_of = |antecedent: char, consequent: char| {
+            explanations[0]
+                .rules
+                .iter()
+                .find(|rule| {
+                    rule.antecedent.len() == 1
+                        && rule.antecedent[0].symbol == antecedent
+                        && rule.consequent == consequent
+                })
+                .unwrap()
+                .connective
+        };
+        // b nearly always follows a, a nearly never does
+        assert_eq!(connective_of('a', 'b'), Connective::Implication);
+        assert_eq!(connective_of('a', 'a'), Connective::ExclusiveOr);
+        // connectives are chosen again from the updated counts
+        let mut updated = CharacterSequencePredictionModel::train_with_connectives(
+            "aabab",
+            2,
+            ConnectiveSelection::BestPerRule,
+        );
+        updated.update("ababab");
+        assert_eq!(updated, model);
+    }
+
+    #[test]
+    fn compress_round_trip() {
+        let text = "nel mezzo del cammin di nostra vita mi ritrovai per una selva oscura, \
+            ché la diritta via era smarrita. ahi quanto a dir qual era è cosa dura";
+        for model in [
+            CharacterSequencePredictionModel::train(text, 4),
+            CharacterSequencePredictionModel::train_with_backoff(text, 3),
+        ] {
+            let compressed = model.compress(text).unwrap();
+            assert_eq!(model.decompress(&compressed).unwrap(), text);
+            // a model trained on the text predicts it well
+            assert!(bits_per_symbol(text.chars().count(), &compressed) < 3.0);
+        }
+        let model = CharacterSequencePredictionModel::train("abc", 2);
+        assert_eq!(model.decompress(&model.compress("").unwrap()).unwrap(), "");
+        assert!(model.compress("abcd").is_err());
+        assert!(model.decompress(&[0, 1]).is_err());
+    }
+
+    #[test]
+    fn classify() {
+        let classifier = Classifier::train(
+            [
+                (
+                    "italiano",
+                    "nel mezzo del cammin di nostra vita mi ritrovai per una selva oscura",
+                ),
+                (
+                    "english",
+                    "in the middle of the journey of our life i found myself in a dark wood",
+                ),
+                ("italiano", "che la diritta via era smarrita"),
+            ],
+            3,
+        );
+        assert_eq!(
+            classifier.labels().collect::<Vec<_>>(),
+            ["italiano", "english"]
+        );
+        let scores = classifier.classify("la via della vita");
+        assert_eq!(scores[0].label, "italiano");
+        assert!(scores[0].confidence > 0.5);
+        let total: f64 = scores.iter().map(|score| score.confidence).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert_eq!(
+            classifier.classify("the life of the wood")[0].label,
+            "english"
+        );
+        let confusion_matrix = classifier.confusion_matrix([
+            ("italiano", "la selva oscura"),
+            ("english", "the dark journey"),
+            ("français", "la vie"),
+        ]);
+        assert_eq!(confusion_matrix.labels, ["italiano", "english", "français"]);
+        assert_eq!(confusion_matrix.counts[0], [1, 0, 0]);
+        assert_eq!(confusion_matrix.counts[1], [0, 1, 0]);
+        assert_eq!(confusion_matrix.counts[2].iter().sum::<usize>(), 1);
+        // an empty text is still a document, between its begin and end
+        assert_eq!(classifier.classify("").len(), 2);
+        let untrained = Classifier::train([], 3);
+        assert!(untrained.classify("la via").is_empty());
+        let confusion_matrix = untrained.confusion_matrix([("italiano", "la via")]);
+        assert_eq!(confusion_matrix.labels, ["italiano"]);
+        assert_eq!(confusion_matrix.counts, [[0]]);
+        assert_eq!(confusion_matrix.accuracy(), 0.0);
+    }
+
+    #[test]
+    fn classifier_confusion_matrix_on_held_out_files() {
+        let directory = std::env::temp_dir();
+        let italian = directory.join("experiment9-classifier-italiano.txt");
+        let english = directory.join("experiment9-classifier-english.txt");
+        std::fs::write(
+            &italian,
+            "la mamma e il papà sono andati al mare con la nonna. ".repeat(8),
+        )
+        .unwrap();
+        std::fs::write(
+            &english,
+            "the mother and the father went to the sea with granny. ".repeat(8),
+        )
+        .unwrap();
+        let (classifier, confusion_matrix) = Classifier::train_files(
+            &[
+                ("italiano", italian.to_str().unwrap()),
+                ("english", english.to_str().unwrap()),
+            ],
+            3,
+            0.25,
+            20,
+        )
+        .unwrap();
+        assert_eq!(classifier.labels().count(), 2);
+        assert_eq!(confusion_matrix.accuracy(), 1.0);
+        assert_eq!(
+            ConfusionMatrix {
+                labels: vec!["a".to_string(), "bb".to_string()],
+                counts: vec![vec![3, 1], vec![0, 12]],
+            }
+            .to_string(),
+            "    a bb\na   3  1\nbb  0 12\naccuracy: 0.938"
+        );
+    }
+
+    #[test]
+    fn surprising_spans() {
+        let model = CharacterSequencePredictionModel::train(
+            "la mamma ama il papà e il papà ama la mamma. "
+                .repeat(4)
+                .as_str(),
+            4,
+        );
+        let text = "il papà ama la mamzzxa e la mamma ama il papà";
+        let surprisal = model.surprisal(text);
+        assert_eq!(surprisal.len(), text.chars().count());
+        // z and x were never seen
+        let spans = model.surprising_spans(text, 10.0);
+        assert_eq!(spans.len(), 1);
+        let garbage: String = text
+            .chars()
+            .skip(spans[0].start)
+            .take(spans[0].end - spans[0].start)
+            .collect();
+        assert_eq!(garbage, "zzx");
+        assert!(spans[0].mean_surprisal > 10.0);
+        assert!(model
+            .surprising_spans("la mamma ama il papà", 10.0)
+            .is_empty());
+        // following characters tell a typo apart from the end of a sentence
+        let bidirectional = CharacterSequencePredictionModel::train_bidirectional(
+            "la mamma ama il papà e il papà ama la mamma. "
+                .repeat(4)
+                .as_str(),
+            4,
+        );
+        let typo = bidirectional.surprisal("la mamma ama il pepà");
+        let (most_surprising, _) = typo
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .unwrap();
+        assert_eq!(most_surprising, 17);
+    }
 }
@@ -0,0 +1,158 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{Error, ErrorKind, Read, Write},
+};
+
+// bump whenever the serialized shape of a model changes
+pub const FORMAT_VERSION: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelFileFormat {
+    Ron,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelFileHeader<Symbol> {
+    pub format_version: u32,
+    pub window_size: usize,
+    pub alphabet: Vec<Symbol>,
+    pub corpus_hash: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ModelFile<Symbol, Model> {
+    header: ModelFileHeader<Symbol>,
+    model: Model,
+}
+
+// only the version is read first, so that files written by another version
+// are rejected before trying to make sense of the rest
+#[derive(Deserialize)]
+struct ModelFileVersion {
+    header: ModelFileHeaderVersion,
+}
+
+#[derive(Deserialize)]
+struct ModelFileHeaderVersion {
+    format_version: u32,
+}
+
+pub fn write_model_file<Symbol: Serialize, Model: Serialize>(
+    file_path: &str,
+    format: ModelFileFormat,
+    header: ModelFileHeader<Symbol>,
+    model: &Model,
+) -> Result<(), Error> {
+    let model_file = ModelFile { header, model };
+    let text = match format {
+        ModelFileFormat::Ron => {
+            ron::ser::to_string_pretty(&model_file, ron::ser::PrettyConfig::default())
+                .map_err(invalid_data)?
+        }
+        ModelFileFormat::Json => serde_json::to_string(&model_file).map_err(invalid_data)?,
+    };
+    let mut file = File::create(file_path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+pub fn read_model_file<Symbol: DeserializeOwned, Model: DeserializeOwned>(
+    file_path: &str,
+    format: ModelFileFormat,
+) -> Result<(ModelFileHeader<Symbol>, Model), Error> {
+    let mut file = File::open(file_path)?;
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    let version: ModelFileVersion = parse(&text, format)?;
+    if version.header.format_version != FORMAT_VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{file_path}: unsupported model file format version {}, expected {FORMAT_VERSION}",
+                version.header.format_version
+            ),
+        ));
+    }
+    let model_file: ModelFile<Symbol, Model> = parse(&text, format)?;
+    Ok((model_file.header, model_file.model))
+}
+
+fn parse<T: DeserializeOwned>(text: &str, format: ModelFileFormat) -> Result<T, Error> {
+    match format {
+        ModelFileFormat::Ron => ron::from_str(text).map_err(invalid_data),
+        ModelFileFormat::Json => serde_json::from_str(text).map_err(invalid_data),
+    }
+}
+
+fn invalid_data(error: impl std::error::Error + Send + Sync + 'static) -> Error {
+    Error::new(ErrorKind::InvalidData, error)
+}
+
+// FNV-1a, a fixed algorithm so that hashes stay comparable across runs and toolchains;
+// integers (char hashes as a u32) are fed little-endian and usize as 64 bits, otherwise Hasher
+// would feed their native bytes and hashes would differ between platforms
+pub struct CorpusHasher(u64);
+
+impl Default for CorpusHasher {
+    fn default() -> Self {
+        CorpusHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for CorpusHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+    fn write_i16(&mut self, value: i16) {
+        self.write_u16(value as u16);
+    }
+    fn write_i32(&mut self, value: i32) {
+        self.write_u32(value as u32);
+    }
+    fn write_i64(&mut self, value: i64) {
+        self.write_u64(value as u64);
+    }
+    fn write_i128(&mut self, value: i128) {
+        self.write_u128(value as u128);
+    }
+    fn write_isize(&mut self, value: isize) {
+        self.write_usize(value as usize);
+    }
+}
+
+pub fn corpus_hash<Symbol: Hash>(data: &[Symbol]) -> u64 {
+    extend_corpus_hash(CorpusHasher::default().finish(), data)
+}
+
+// the hash of a corpus followed by data, given the hash of the corpus
+pub fn extend_corpus_hash<Symbol: Hash>(corpus_hash: u64, data: &[Symbol]) -> u64 {
+    let mut hasher = CorpusHasher(corpus_hash);
+    for symbol in data {
+        symbol.hash(&mut hasher);
+    }
+    hasher.finish()
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+// a rule is removed by the first filter it does not pass, None disables a filter
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pruning {
+    // how many times the condition occurred
+    pub minimum_support: Option<u32>,
+    // how many times the consequence followed, relative to the support
+    pub minimum_confidence: Option<f64>,
+    // confidence * support, as defined in NOTES-A
+    pub minimum_utility: Option<f64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PruningReport {
+    pub removed_by_support: usize,
+    pub removed_by_confidence: usize,
+    pub removed_by_utility: usize,
+    pub kept: usize,
+}
+
+impl Pruning {
+    // whether a rule with these counts passes every filter, the outcome is added to report
+    pub fn keep(
+        &self,
+        condition_count: u32,
+        consequence_count: u32,
+        report: &mut PruningReport,
+    ) -> bool {
+        let confidence = if condition_count == 0 {
+            0.0
+        } else {
+            consequence_count as f64 / condition_count as f64
+        };
+        let utility = confidence * condition_count as f64;
+        if self
+            .minimum_support
+            .is_some_and(|minimum| condition_count < minimum)
+        {
+            report.removed_by_support += 1;
+            false
+        } else if self
+            .minimum_confidence
+            .is_some_and(|minimum| confidence < minimum)
+        {
+            report.removed_by_confidence += 1;
+            false
+        } else if self
+            .minimum_utility
+            .is_some_and(|minimum| utility < minimum)
+        {
+            report.removed_by_utility += 1;
+            false
+        } else {
+            report.kept += 1;
+            true
+        }
+    }
+}
+
+// rows of a CountMinSketch, each one with its own hash of the items
+const COUNT_MIN_SKETCH_DEPTH: usize = 4;
+
+// how many times each item was added, estimated in a fixed amount of memory however many
+// distinct items there are, never less than the actual count
+// https://en.wikipedia.org/wiki/Count%E2%80%93min_sketch
+#[derive(Debug, Clone)]
+pub struct CountMinSketch {
+    width: usize,
+    counts: Vec<u32>,
+}
+
+impl CountMinSketch {
+    pub fn new(width: usize) -> CountMinSketch {
+        assert!(width > 0);
+        CountMinSketch {
+            width,
+            counts: vec![0; COUNT_MIN_SKETCH_DEPTH * width],
+        }
+    }
+    // one cell in each row
+    fn cells<Item: Hash>(&self, item: &Item) -> Vec<usize> {
+        (0..COUNT_MIN_SKETCH_DEPTH)
+            .map(|row| {
+                let mut hasher = DefaultHasher::new();
+                row.hash(&mut hasher);
+                item.hash(&mut hasher);
+                row * self.width + (hasher.finish() % self.width as u64) as usize
+            })
+            .collect()
+    }
+    pub fn add<Item: Hash>(&mut self, item: &Item) {
+        for cell in self.cells(item) {
+            self.counts[cell] = self.counts[cell].saturating_add(1);
+        }
+    }
+    // other items sharing a cell can only add to it, so the smallest cell is the closest
+    pub fn estimate<Item: Hash>(&self, item: &Item) -> u32 {
+        self.cells(item)
+            .into_iter()
+            .map(|cell| self.counts[cell])
+            .min()
+            .unwrap()
+    }
+}
@@ -0,0 +1,94 @@
+// splitmix64, enough to make sampling reproducible from a seed without extra dependencies
+#[derive(Debug, Clone)]
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random(seed)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+    // uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    // 1.0 keeps the distribution, lower sharpens it, 0.0 is argmax
+    pub temperature: f64,
+    // keep only the k most probable symbols
+    pub top_k: Option<usize>,
+    // keep the most probable symbols until their cumulative probability reaches p
+    pub top_p: Option<f64>,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling {
+            temperature: 1.0,
+            top_k: None,
+            top_p: None,
+        }
+    }
+}
+
+impl Sampling {
+    pub fn sample<Symbol: Copy>(
+        &self,
+        distribution: &[(Symbol, f64)],
+        random: &mut Random,
+    ) -> Option<Symbol> {
+        let mut candidates: Vec<(Symbol, f64)> = distribution
+            .iter()
+            .copied()
+            .filter(|(_, probability)| *probability > 0.0)
+            .collect();
+        // stable, so equally probable symbols keep the order of the distribution
+        candidates.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        if self.temperature <= 0.0 {
+            return candidates.first().map(|(symbol, _)| *symbol);
+        }
+        for (_, probability) in candidates.iter_mut() {
+            *probability = probability.powf(1.0 / self.temperature);
+        }
+        if let Some(top_k) = self.top_k {
+            candidates.truncate(top_k.max(1));
+        }
+        Self::normalize(&mut candidates);
+        if let Some(top_p) = self.top_p {
+            let mut cumulative = 0.0;
+            let mut kept = 0;
+            for (_, probability) in &candidates {
+                kept += 1;
+                cumulative += probability;
+                if cumulative >= top_p {
+                    break;
+                }
+            }
+            candidates.truncate(kept);
+            Self::normalize(&mut candidates);
+        }
+        let mut threshold = random.next_f64();
+        for (symbol, probability) in &candidates {
+            if threshold < *probability {
+                return Some(*symbol);
+            }
+            threshold -= probability;
+        }
+        // rounding errors can leave the threshold just above the last probability
+        candidates.last().map(|(symbol, _)| *symbol)
+    }
+    fn normalize<Symbol>(candidates: &mut [(Symbol, f64)]) {
+        let total: f64 = candidates.iter().map(|(_, probability)| probability).sum();
+        for (_, probability) in candidates.iter_mut() {
+            *probability /= total;
+        }
+    }
+}
@@ -1,110 +1,1128 @@
 use super::boolean_algebra::*;
-use std::{collections::HashSet, fmt::Debug, hash::Hash};
+use super::corpus::*;
+use super::evaluation::MINIMUM_PROBABILITY;
+use super::explanation::*;
+use super::model_file::*;
+use super::pruning::*;
+use super::sampling::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+    io::Error,
+};
 
-#[derive(Debug)]
-pub struct SequencePredictionModel<Symbol: Copy> {
-    rules: Vec<(Rule<SymbolAtRelativeIndex<Symbol>>, f64)>,
+// every subset of the symbols a rule can look at is an antecedent, so training counts
+// 2^(window_size - 1 + lookahead) of them at each position
+pub const MAX_ANTECEDENT_TERMS: usize = 16;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "Symbol: Deserialize<'de>"))]
+pub struct SequencePredictionModel<Symbol: Copy + Eq + Hash> {
+    // window size, alphabet and corpus hash are stored in the model file header
+    #[serde(skip)]
+    window_size: usize,
+    #[serde(skip)]
+    alphabet: Vec<Symbol>,
+    #[serde(skip)]
+    corpus_hash: u64,
+    rules: Vec<(SymbolRule<Symbol>, RuleStats)>,
+    prediction: Prediction,
+    connective_selection: ConnectiveSelection,
+    // how many symbols after a position its rules can look at, 0 unless trained bidirectionally
+    lookahead: usize,
+    // the last window_size - 1 training symbols, the context of the first updated position
+    tail: Vec<Symbol>,
+    // rules by the first term of their antecedent (None for the empty one): a rule can fire
+    // only if the symbol at that relative index matches, derived from the rules
+    #[serde(skip)]
+    rules_index: HashMap<Option<(i32, Symbol)>, Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Prediction {
+    // the most confident firing rule decides
+    BestRule,
+    // PPM-style blending: the longest seen context first, escaping to shorter ones
+    // with probability distinct symbols / (occurrences + distinct symbols)
+    Backoff { max_order: usize },
 }
 
+// how the connective between the antecedent and the consequent of each rule is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConnectiveSelection {
+    // the same for every rule
+    Fixed(Connective),
+    // the one holding at the most training positions, per rule, among the ones saying
+    // something about the consequent when the rule fires (the only rules prediction looks at)
+    BestPerRule,
+}
+
+type SymbolRule<Symbol> =
+    Rule<Conjunction<SymbolAtRelativeIndex<Symbol>>, SymbolAtRelativeIndex<Symbol>>;
+
 impl<Symbol: Copy + Eq + Hash + Debug> SequencePredictionModel<Symbol> {
-    pub fn train(data: &Vec<Symbol>, window_size: usize) -> SequencePredictionModel<Symbol> {
+    // a single pass over the data counting every rule observed in it
+    pub fn train(data: &[Symbol], window_size: usize) -> SequencePredictionModel<Symbol> {
+        let mut model = Self::untrained(window_size, 0);
+        model.update(data);
+        model
+    }
+    // rules also look at the window_size - 1 symbols following a position, for infilling
+    pub fn train_bidirectional(
+        data: &[Symbol],
+        window_size: usize,
+    ) -> SequencePredictionModel<Symbol> {
+        let mut model = Self::untrained(window_size, window_size - 1);
+        model.update(data);
+        model
+    }
+    pub fn train_corpus(
+        corpus: &Corpus<Symbol>,
+        window_size: usize,
+    ) -> SequencePredictionModel<Symbol> {
+        let mut model = Self::untrained(window_size, 0);
+        for document in corpus.bounded_documents() {
+            model.add_document(&document);
+        }
+        model
+    }
+    // connective_selection chooses the connective of each rule (implication for the others)
+    pub fn train_with_connectives(
+        data: &[Symbol],
+        window_size: usize,
+        connective_selection: ConnectiveSelection,
+    ) -> SequencePredictionModel<Symbol> {
+        let mut model = SequencePredictionModel {
+            connective_selection,
+            ..Self::untrained(window_size, 0)
+        };
+        model.update(data);
+        model
+    }
+    fn untrained(window_size: usize, lookahead: usize) -> SequencePredictionModel<Symbol> {
         assert!(window_size > 1);
-        let alphabet = Self::derive_alphabet_from_data(data);
+        assert!(
+            window_size - 1 + lookahead <= MAX_ANTECEDENT_TERMS,
+            "rules can look at {} symbols at most",
+            MAX_ANTECEDENT_TERMS
+        );
         SequencePredictionModel {
-            rules: alphabet
+            window_size,
+            alphabet: Vec::new(),
+            corpus_hash: corpus_hash::<Symbol>(&[]),
+            rules: Vec::new(),
+            prediction: Prediction::BestRule,
+            connective_selection: ConnectiveSelection::Fixed(Connective::Implication),
+            lookahead,
+            tail: Vec::new(),
+            rules_index: HashMap::new(),
+        }
+    }
+    // with max_order 0 only the frequency of each symbol counts, the rules of the window of 2
+    // looking at the previous symbol are never used
+    pub fn train_with_backoff(
+        data: &[Symbol],
+        max_order: usize,
+    ) -> SequencePredictionModel<Symbol> {
+        SequencePredictionModel {
+            prediction: Prediction::Backoff { max_order },
+            ..Self::train(data, max_order.max(1) + 1)
+        }
+    }
+    // only the last window_size - 1 symbols of a sequence matter for predicting the next one
+    pub fn window_size(&self) -> usize {
+        self.window_size
+    }
+    // every symbol seen in training, in order of first occurrence
+    pub fn alphabet(&self) -> &[Symbol] {
+        &self.alphabet
+    }
+    // like train and then prune, but the rules of the antecedents estimated to occur fewer than
+    // minimum_support times are not even counted, so they never take memory; removed_by_support
+    // only has the ones the estimate lets through
+    pub fn train_pruned(
+        data: &[Symbol],
+        window_size: usize,
+        pruning: &Pruning,
+    ) -> (SequencePredictionModel<Symbol>, PruningReport) {
+        let mut model = Self::untrained(window_size, 0);
+        match pruning.minimum_support {
+            Some(minimum_support) => {
+                // as wide as there are antecedent occurrences (up to 2^20), so that few collide
+                let occurrences: usize = (0..data.len())
+                    .map(|index| 1usize << (window_size - 1).min(index))
+                    .sum();
+                let mut antecedent_counts =
+                    CountMinSketch::new(occurrences.next_power_of_two().clamp(1 << 8, 1 << 20));
+                for index in 0..data.len() {
+                    for antecedent in Self::antecedents_at(data, index, window_size, 0) {
+                        antecedent_counts.add(&antecedent);
+                    }
+                }
+                // prune keeps the rules with the empty antecedent, whatever their support
+                model.count(data, |antecedent| {
+                    antecedent.0.is_empty()
+                        || antecedent_counts.estimate(antecedent) >= minimum_support
+                });
+            }
+            None => model.update(data),
+        }
+        let report = model.prune(pruning);
+        (model, report)
+    }
+    // folds new observations into the counts, same result as training on the concatenated data;
+    // after prune the rules it removed come back with the new observations only
+    pub fn update(&mut self, new_data: &[Symbol]) {
+        self.count(new_data, |_| true);
+    }
+    // like update, only the rules with an antecedent passing counted
+    fn count(
+        &mut self,
+        new_data: &[Symbol],
+        counted: impl Fn(&Conjunction<SymbolAtRelativeIndex<Symbol>>) -> bool,
+    ) {
+        // the last positions were counted without the symbols that would follow them
+        assert!(
+            self.lookahead == 0 || self.tail.is_empty(),
+            "a bidirectional model can only be trained at once or one document at a time"
+        );
+        let data: Vec<Symbol> = self.tail.iter().chain(new_data.iter()).copied().collect();
+        let mut antecedent_counts: HashMap<Conjunction<SymbolAtRelativeIndex<Symbol>>, u32> =
+            HashMap::new();
+        let mut observed_rules: Vec<(SymbolRule<Symbol>, u32)> = Vec::new();
+        let mut observed_rules_index: HashMap<SymbolRule<Symbol>, usize> = HashMap::new();
+        for index in self.tail.len()..data.len() {
+            for antecedent in Self::antecedents_at(&data, index, self.window_size, self.lookahead) {
+                if !counted(&antecedent) {
+                    continue;
+                }
+                *antecedent_counts.entry(antecedent.clone()).or_default() += 1;
+                let rule = Rule(
+                    antecedent,
+                    SymbolAtRelativeIndex {
+                        symbol: data[index],
+                        relative_index: 0,
+                    },
+                );
+                if let Some(rule_index) = observed_rules_index.get(&rule) {
+                    observed_rules[*rule_index].1 += 1;
+                } else {
+                    observed_rules_index.insert(rule.clone(), observed_rules.len());
+                    observed_rules.push((rule, 1));
+                }
+            }
+        }
+        // rules sharing an antecedent share its count, so any of them tells the previous one
+        let previous_antecedent_counts: HashMap<Conjunction<SymbolAtRelativeIndex<Symbol>>, u32> =
+            self.rules
                 .iter()
-                .flat_map(|current_symbol| {
-                    alphabet.iter().flat_map(|previous_symbol| {
-                        (1..window_size)
-                            .map(|offset| -(offset as i32))
-                            .map(|negative_offset| {
-                                let rule = Rule(
-                                    SymbolAtRelativeIndex {
-                                        symbol: *previous_symbol,
-                                        relative_index: negative_offset,
-                                    },
-                                    SymbolAtRelativeIndex {
-                                        symbol: *current_symbol,
-                                        relative_index: 0,
-                                    },
-                                );
-                                let value =
-                                    rule.evaluate((0..data.len()).map(|index| (data, index)));
-                                (rule, value)
-                            })
+                .map(|(rule, stats)| (rule.0.clone(), stats.antecedent_count))
+                .collect();
+        let mut existing_rules_index: HashMap<SymbolRule<Symbol>, usize> = HashMap::new();
+        for (rule_index, (rule, stats)) in self.rules.iter_mut().enumerate() {
+            stats.antecedent_count += antecedent_counts.get(&rule.0).copied().unwrap_or(0);
+            existing_rules_index.insert(rule.clone(), rule_index);
+        }
+        for (rule, rule_count) in observed_rules {
+            if let Some(rule_index) = existing_rules_index.get(&rule) {
+                self.rules[*rule_index].1.rule_count += rule_count;
+            } else {
+                let antecedent_count = previous_antecedent_counts
+                    .get(&rule.0)
+                    .copied()
+                    .unwrap_or(0)
+                    + antecedent_counts[&rule.0];
+                self.rules.push((
+                    rule,
+                    RuleStats {
+                        antecedent_count,
+                        rule_count,
+                        connective: Connective::default(),
+                    },
+                ));
+            }
+        }
+        for symbol in Self::derive_alphabet_from_data(new_data) {
+            if !self.alphabet.contains(&symbol) {
+                self.alphabet.push(symbol);
+            }
+        }
+        self.corpus_hash = extend_corpus_hash(self.corpus_hash, new_data);
+        self.tail = data[data.len().saturating_sub(self.window_size - 1)..].to_vec();
+        self.choose_connectives();
+        self.index_rules();
+    }
+    // counts change the best connectives, so they are chosen again after every update
+    fn choose_connectives(&mut self) {
+        // the empty antecedent holds at every position, its rules count each symbol
+        let mut position_count = 0;
+        let mut consequent_counts: HashMap<Symbol, u32> = HashMap::new();
+        for (rule, stats) in &self.rules {
+            if rule.0 .0.is_empty() {
+                position_count = stats.antecedent_count;
+                consequent_counts.insert(rule.1.symbol, stats.rule_count);
+            }
+        }
+        let connective_selection = self.connective_selection;
+        for (rule, stats) in self.rules.iter_mut() {
+            stats.connective = match connective_selection {
+                ConnectiveSelection::Fixed(connective) => connective,
+                // on ties the first in Connective::ALL
+                ConnectiveSelection::BestPerRule => Connective::ALL
+                    .into_iter()
+                    .filter(|connective| connective.consequent_when_antecedent().is_some())
+                    .rev()
+                    .max_by_key(|connective| {
+                        stats.holding_count(
+                            *connective,
+                            consequent_counts[&rule.1.symbol],
+                            position_count,
+                        )
                     })
-                })
-                .collect(),
+                    .unwrap(),
+            };
+        }
+    }
+    // removes the rules not passing pruning, except the ones with the empty antecedent that
+    // prediction falls back to; meant after the last update, since removed rules would start
+    // counting again from zero
+    pub fn prune(&mut self, pruning: &Pruning) -> PruningReport {
+        let mut report = PruningReport::default();
+        self.rules.retain(|(rule, stats)| {
+            rule.0 .0.is_empty()
+                || pruning.keep(stats.antecedent_count, stats.rule_count, &mut report)
+        });
+        self.index_rules();
+        report
+    }
+    // counts document on its own, without the context of what was trained before it
+    pub fn add_document(&mut self, document: &[Symbol]) {
+        self.tail.clear();
+        self.update(document);
+        self.tail.clear();
+    }
+    pub fn save(&self, file_path: &str, format: ModelFileFormat) -> Result<(), Error>
+    where
+        Symbol: Serialize,
+    {
+        write_model_file(file_path, format, self.header(), self)
+    }
+    pub fn load(file_path: &str, format: ModelFileFormat) -> Result<Self, Error>
+    where
+        Symbol: DeserializeOwned,
+    {
+        let (header, model): (ModelFileHeader<Symbol>, Self) = read_model_file(file_path, format)?;
+        Ok(model.with_header(header))
+    }
+    // what is stored in the model file header instead of with the rules
+    pub fn header(&self) -> ModelFileHeader<Symbol> {
+        ModelFileHeader {
+            format_version: FORMAT_VERSION,
+            window_size: self.window_size,
+            alphabet: self.alphabet.clone(),
+            corpus_hash: self.corpus_hash,
         }
     }
+    // completes a model deserialized without its header
+    pub fn with_header(self, header: ModelFileHeader<Symbol>) -> Self {
+        let mut model = SequencePredictionModel {
+            window_size: header.window_size,
+            alphabet: header.alphabet,
+            corpus_hash: header.corpus_hash,
+            ..self
+        };
+        model.index_rules();
+        model
+    }
     pub fn predict(&self, sequence: &mut Vec<Symbol>, length: usize) {
         for _ in 0..length {
-            let next_symbol = self.predict_next_symbol(&sequence);
-            sequence.push(next_symbol);
+            match self.predict_next_symbol(sequence) {
+                Some(next_symbol) => sequence.push(next_symbol),
+                None => break,
+            }
         }
     }
-    fn predict_next_symbol(&self, sequence: &Vec<Symbol>) -> Symbol {
-        let current_index = sequence.len();
-        // println!("seuqnce: {:?}", sequence);
-        self.rules
-            .iter()
-            .map(|(rule, value)| {
-                let new_value = if rule.0.evaluate((sequence, current_index)) {
-                    *value
-                } else {
-                    1.0 - *value
+    // like predict, stopping before end when it is predicted
+    pub fn predict_until(&self, sequence: &mut Vec<Symbol>, max_length: usize, end: Symbol) {
+        for _ in 0..max_length {
+            match self.predict_next_symbol(sequence) {
+                Some(next_symbol) if next_symbol != end => sequence.push(next_symbol),
+                _ => break,
+            }
+        }
+    }
+    pub fn sample(
+        &self,
+        sequence: &mut Vec<Symbol>,
+        length: usize,
+        sampling: &Sampling,
+        random: &mut Random,
+    ) {
+        for _ in 0..length {
+            match sampling.sample(&self.predict_distribution(sequence, None), random) {
+                Some(next_symbol) => sequence.push(next_symbol),
+                None => break,
+            }
+        }
+    }
+    // keeps the beam_width continuations with the highest accumulated log probability at each
+    // step, returns the best count of them (without the initial sequence) with their scores
+    pub fn predict_beam(
+        &self,
+        sequence: &[Symbol],
+        length: usize,
+        beam_width: usize,
+        count: usize,
+    ) -> Vec<(Vec<Symbol>, f64)> {
+        let mut beams: Vec<(Vec<Symbol>, f64)> = vec![(sequence.to_vec(), 0.0)];
+        for _ in 0..length {
+            let mut candidates: Vec<(Vec<Symbol>, f64)> = Vec::new();
+            for (beam, score) in &beams {
+                for (symbol, probability) in self.predict_distribution(beam, None) {
+                    if probability > 0.0 {
+                        let mut candidate = beam.clone();
+                        candidate.push(symbol);
+                        candidates.push((candidate, score + probability.ln()));
+                    }
+                }
+            }
+            candidates.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+            candidates.truncate(beam_width);
+            beams = candidates;
+        }
+        beams
+            .into_iter()
+            .take(count)
+            .map(|(beam, score)| (beam[sequence.len()..].to_vec(), score))
+            .collect()
+    }
+    // like predict, with the explanation of each predicted symbol
+    pub fn predict_explained(
+        &self,
+        sequence: &mut Vec<Symbol>,
+        length: usize,
+    ) -> Vec<Explanation<Symbol>> {
+        (0..length)
+            .map_while(|_| {
+                let explanation = self.explain_next_symbol(sequence)?;
+                sequence.push(explanation.prediction);
+                Some(explanation)
+            })
+            .collect()
+    }
+    // None when nothing can be predicted, like for a model trained on no data
+    pub fn explain_next_symbol(&self, sequence: &[Symbol]) -> Option<Explanation<Symbol>> {
+        let (rules, deciding_rule): (Vec<(usize, bool)>, Option<usize>) = match self.prediction {
+            // every rule that could have fired, the best of the fired ones decides
+            Prediction::BestRule => {
+                let rules: Vec<(usize, bool)> = self
+                    .candidate_rules_at(sequence, sequence.len())
+                    .into_iter()
+                    .map(|rule_index| {
+                        let fired = self.rules[rule_index]
+                            .0
+                             .0
+                            .evaluate((sequence, sequence.len()));
+                        (rule_index, fired)
+                    })
+                    .collect();
+                let fired: Vec<usize> = rules
+                    .iter()
+                    .filter(|(_, fired)| *fired)
+                    .map(|(rule_index, _)| *rule_index)
+                    .collect();
+                let deciding_rule = self.deciding_rule(&fired).map(|deciding_rule_index| {
+                    rules
+                        .iter()
+                        .position(|(rule_index, _)| *rule_index == deciding_rule_index)
+                        .unwrap()
+                });
+                (rules, deciding_rule)
+            }
+            // the rules of every context blended, none decides alone
+            Prediction::Backoff { max_order } => (
+                (0..=max_order.min(sequence.len()))
+                    .flat_map(|order| self.context_rules(Self::context(sequence, order)))
+                    .map(|rule_index| (rule_index, true))
+                    .collect(),
+                None,
+            ),
+        };
+        Some(Explanation {
+            prediction: self.predict_next_symbol(sequence)?,
+            candidates: self
+                .predict_distribution(sequence, None)
+                .into_iter()
+                .map(|(symbol, score)| CandidateScore { symbol, score })
+                .collect(),
+            rules: rules
+                .into_iter()
+                .map(|(rule_index, fired)| {
+                    let (rule, stats) = &self.rules[rule_index];
+                    ExplainedRule {
+                        antecedent: rule
+                            .0
+                             .0
+                            .iter()
+                            .map(|term| ExplainedTerm {
+                                relative_index: term.relative_index,
+                                symbol: term.symbol,
+                            })
+                            .collect(),
+                        consequent: rule.1.symbol,
+                        antecedent_count: stats.antecedent_count,
+                        rule_count: stats.rule_count,
+                        confidence: stats.confidence(),
+                        connective: stats.connective,
+                        fired,
+                    }
+                })
+                .collect(),
+            deciding_rule,
+        })
+    }
+    // fills the holes (None) of sequence, the one with the most confident rule first,
+    // so that every filled symbol becomes context for the remaining holes, only rules
+    // asserting their consequent fill holes
+    pub fn infill(&self, sequence: &mut [Option<Symbol>]) {
+        loop {
+            let best = (0..sequence.len())
+                .filter(|index| sequence[*index].is_none())
+                .filter_map(|index| {
+                    Self::best_rule(
+                        self.infilling_rules(sequence, index)
+                            .filter(|(_, stats)| stats.asserts_consequent()),
+                    )
+                    .map(|rule| (index, rule))
+                })
+                // on equally good rules the leftmost hole goes first
+                .rev()
+                .max_by(|(_, a), (_, b)| Self::compare_rules(a, b));
+            match best {
+                Some((index, (rule, _))) => sequence[index] = Some(rule.1.symbol),
+                None => break,
+            }
+        }
+    }
+    // -log2 P(symbol) in bits for every symbol of data, predicted from the symbols around it
+    // (only the preceding ones unless the model is bidirectional), symbols the model gives no
+    // probability to are charged MINIMUM_PROBABILITY
+    pub fn surprisal(&self, data: &[Symbol]) -> Vec<f64> {
+        (0..data.len())
+            .map(|index| {
+                let distribution = match self.prediction {
+                    Prediction::BestRule => {
+                        self.distribution_of_rules(self.rules_firing_at(data, index))
+                    }
+                    Prediction::Backoff { max_order } => self.backoff_distribution(
+                        &data[index.saturating_sub(max_order)..index],
+                        max_order,
+                    ),
                 };
-                // println!("{:?} {} {}", rule, value, new_value);
-                (rule, new_value)
+                let probability = distribution
+                    .iter()
+                    .find(|(symbol, _)| *symbol == data[index])
+                    .map_or(0.0, |(_, probability)| *probability);
+                -probability.max(MINIMUM_PROBABILITY).log2()
             })
-            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-            .map(|(rule, _)| rule.1.symbol)
+            .collect()
+    }
+    // every symbol of the alphabet with its probability of coming next, most probable first,
+    // the ones below minimum_probability are left out (the others are not renormalized)
+    pub fn predict_distribution(
+        &self,
+        sequence: &[Symbol],
+        minimum_probability: Option<f64>,
+    ) -> Vec<(Symbol, f64)> {
+        let mut distribution = match self.prediction {
+            Prediction::BestRule => {
+                // equally probable symbols are ordered as deciding_rule ranks their strongest
+                // rules, so that the first one is the symbol predict goes for
+                let firing = self.firing_rule_indices(sequence);
+                let strongest = self.strongest_assertions(&firing);
+                let mut distribution = self.distribution_of_rules(firing);
+                distribution.sort_by(|(symbol_a, a), (symbol_b, b)| {
+                    b.partial_cmp(a).unwrap().then_with(|| {
+                        match (strongest.get(symbol_a), strongest.get(symbol_b)) {
+                            (Some(a), Some(b)) => {
+                                Self::compare_rules(&self.rules[*b], &self.rules[*a])
+                            }
+                            (a, b) => b.is_some().cmp(&a.is_some()),
+                        }
+                    })
+                });
+                distribution
+            }
+            Prediction::Backoff { max_order } => {
+                let mut distribution = self.backoff_distribution(sequence, max_order);
+                distribution.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+                distribution
+            }
+        };
+        // the sorts are stable, remaining ties keep the alphabet order
+        distribution.retain(|(_, probability)| *probability >= minimum_probability.unwrap_or(0.0));
+        distribution
+    }
+    // None only with an empty alphabet
+    fn predict_next_symbol(&self, sequence: &[Symbol]) -> Option<Symbol> {
+        match self.prediction {
+            Prediction::BestRule => self.predict_next_symbol_by_best_rule(sequence),
+            Prediction::Backoff { .. } => self
+                .predict_distribution(sequence, None)
+                .first()
+                .map(|(symbol, _)| *symbol),
+        }
+    }
+    // without a deciding rule, the symbol least confidently asserted not to follow
+    fn predict_next_symbol_by_best_rule(&self, sequence: &[Symbol]) -> Option<Symbol> {
+        let firing = self.firing_rule_indices(sequence);
+        match self.deciding_rule(&firing) {
+            Some(rule_index) => Some(self.rules[rule_index].0 .1.symbol),
+            None => {
+                let vetoes = self.vetoes(&firing);
+                let veto = |symbol: &Symbol| vetoes.get(symbol).copied().unwrap_or(0.0);
+                self.alphabet
+                    .iter()
+                    .min_by(|a, b| veto(a).partial_cmp(&veto(b)).unwrap())
+                    .copied()
+            }
+        }
+    }
+    // the best of the firing rules asserting their consequent, unless a more confident one
+    // asserts that consequent does not follow
+    fn deciding_rule(&self, firing: &[usize]) -> Option<usize> {
+        let vetoes = self.vetoes(firing);
+        let asserting = firing
+            .iter()
+            .copied()
+            .filter(|rule_index| self.rules[*rule_index].1.asserts_consequent());
+        let best = |rule_indices: Vec<usize>| {
+            rule_indices
+                .into_iter()
+                .max_by(|a, b| self.compare_asserting_rules(*a, *b))
+        };
+        best(
+            asserting
+                .clone()
+                .filter(|rule_index| {
+                    let (rule, stats) = &self.rules[*rule_index];
+                    vetoes
+                        .get(&rule.1.symbol)
+                        .is_none_or(|veto| *veto <= stats.confidence())
+                })
+                .collect(),
+        )
+        .or_else(|| best(asserting.collect()))
+    }
+    // the confidence of the strongest firing rule asserting each symbol does not follow
+    fn vetoes(&self, firing: &[usize]) -> HashMap<Symbol, f64> {
+        let mut vetoes: HashMap<Symbol, f64> = HashMap::new();
+        for rule_index in firing {
+            let (rule, stats) = &self.rules[*rule_index];
+            if let Some((false, confidence)) = stats.assertion() {
+                let veto = vetoes.entry(rule.1.symbol).or_default();
+                *veto = veto.max(confidence);
+            }
+        }
+        vetoes
+    }
+    // the strongest firing rule asserting each symbol
+    fn strongest_assertions(&self, firing: &[usize]) -> HashMap<Symbol, usize> {
+        let mut strongest: HashMap<Symbol, usize> = HashMap::new();
+        for rule_index in firing {
+            if self.rules[*rule_index].1.asserts_consequent() {
+                let symbol = self.rules[*rule_index].0 .1.symbol;
+                let current = strongest.entry(symbol).or_insert(*rule_index);
+                if self.compare_asserting_rules(*rule_index, *current) == Ordering::Greater {
+                    *current = *rule_index;
+                }
+            }
+        }
+        strongest
+    }
+    // compare_rules, and on equally good rules the one asserting the symbol first in the
+    // alphabet, the order in which predict_distribution leaves equally probable symbols
+    fn compare_asserting_rules(&self, rule_index_a: usize, rule_index_b: usize) -> Ordering {
+        let position = |rule_index: usize| {
+            self.alphabet
+                .iter()
+                .position(|symbol| *symbol == self.rules[rule_index].0 .1.symbol)
+        };
+        Self::compare_rules(&self.rules[rule_index_a], &self.rules[rule_index_b])
+            .then_with(|| position(rule_index_b).cmp(&position(rule_index_a)))
+    }
+    fn best_rule<'a>(
+        rules: impl Iterator<Item = &'a (SymbolRule<Symbol>, RuleStats)>,
+    ) -> Option<&'a (SymbolRule<Symbol>, RuleStats)>
+    where
+        Symbol: 'a,
+    {
+        rules.max_by(|a, b| Self::compare_rules(a, b))
+    }
+    // the most confident rule wins, ties go to the better supported and then more specific one,
+    // for rules asserting their consequent
+    fn compare_rules(
+        (rule_a, stats_a): &(SymbolRule<Symbol>, RuleStats),
+        (rule_b, stats_b): &(SymbolRule<Symbol>, RuleStats),
+    ) -> Ordering {
+        stats_a
+            .confidence()
+            .partial_cmp(&stats_b.confidence())
             .unwrap()
+            .then(stats_a.antecedent_count.cmp(&stats_b.antecedent_count))
+            .then(rule_a.0 .0.len().cmp(&rule_b.0 .0.len()))
     }
-    fn derive_alphabet_from_data(data: &Vec<Symbol>) -> HashSet<Symbol> {
-        data.iter().copied().collect()
+    // each symbol is scored by its most confident firing rule asserting it, times one minus
+    // the confidence of the strongest one asserting it does not follow, then scores are
+    // normalized
+    fn distribution_of_rules(&self, firing: Vec<usize>) -> Vec<(Symbol, f64)> {
+        let vetoes = self.vetoes(&firing);
+        let mut distribution: Vec<(Symbol, f64)> =
+            self.alphabet.iter().map(|symbol| (*symbol, 0.0)).collect();
+        for rule_index in firing {
+            let (rule, stats) = &self.rules[rule_index];
+            if let (Some((true, confidence)), Some((_, score))) = (
+                stats.assertion(),
+                distribution
+                    .iter_mut()
+                    .find(|(symbol, _)| *symbol == rule.1.symbol),
+            ) {
+                *score = score.max(confidence);
+            }
+        }
+        for (symbol, score) in distribution.iter_mut() {
+            *score *= 1.0 - vetoes.get(symbol).copied().unwrap_or(0.0);
+        }
+        let total: f64 = distribution.iter().map(|(_, score)| score).sum();
+        for (_, score) in distribution.iter_mut() {
+            *score = if total > 0.0 {
+                *score / total
+            } else {
+                1.0 / self.alphabet.len() as f64
+            };
+        }
+        distribution
+    }
+    // P(symbol) = count(context, symbol) / (occurrences + distinct) + escape * P_shorter(symbol)
+    // with escape = distinct / (occurrences + distinct)
+    // starting from a uniform distribution over the alphabet
+    fn backoff_distribution(&self, sequence: &[Symbol], max_order: usize) -> Vec<(Symbol, f64)> {
+        let mut distribution: Vec<(Symbol, f64)> = self
+            .alphabet
+            .iter()
+            .map(|symbol| (*symbol, 1.0 / self.alphabet.len() as f64))
+            .collect();
+        for order in 0..=max_order.min(sequence.len()) {
+            let observed: Vec<(Symbol, RuleStats)> = self
+                .context_rules(Self::context(sequence, order))
+                .map(|rule_index| &self.rules[rule_index])
+                .map(|(rule, stats)| (rule.1.symbol, *stats))
+                .collect();
+            if !observed.is_empty() {
+                // the kept counts rather than antecedent_count: the symbols of pruned rules go
+                // to the escape like unseen ones, so that the distribution still sums to 1
+                let occurrences: u32 = observed.iter().map(|(_, stats)| stats.rule_count).sum();
+                let denominator = (occurrences as usize + observed.len()) as f64;
+                let escape = observed.len() as f64 / denominator;
+                for (symbol, probability) in distribution.iter_mut() {
+                    let count = observed
+                        .iter()
+                        .find(|(observed_symbol, _)| observed_symbol == symbol)
+                        .map(|(_, stats)| stats.rule_count)
+                        .unwrap_or(0);
+                    *probability = count as f64 / denominator + escape * *probability;
+                }
+            }
+        }
+        distribution
+    }
+    // every conjunction of the symbols preceding index within the window (and of the
+    // lookahead ones following it), including the empty one: exponential in the window size,
+    // which untrained bounds with MAX_ANTECEDENT_TERMS
+    fn antecedents_at(
+        data: &[Symbol],
+        index: usize,
+        window_size: usize,
+        lookahead: usize,
+    ) -> Vec<Conjunction<SymbolAtRelativeIndex<Symbol>>> {
+        let relative_indices: Vec<i32> = (1..=(window_size - 1).min(index))
+            .map(|offset| -(offset as i32))
+            .chain((1..=lookahead.min(data.len() - 1 - index)).map(|offset| offset as i32))
+            .collect();
+        (0..(1usize << relative_indices.len()))
+            .map(|mask| {
+                Conjunction(
+                    relative_indices
+                        .iter()
+                        .enumerate()
+                        .filter(|(bit, _)| mask & (1 << bit) != 0)
+                        .map(|(_, relative_index)| SymbolAtRelativeIndex {
+                            symbol: data[(index as i32 + relative_index) as usize],
+                            relative_index: *relative_index,
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+    // the rules that fire at the end of sequence, in rule order
+    #[cfg(test)]
+    fn firing_rules<'a>(
+        &'a self,
+        sequence: &'a [Symbol],
+    ) -> impl Iterator<Item = &'a (SymbolRule<Symbol>, RuleStats)> + 'a {
+        self.firing_rule_indices(sequence)
+            .into_iter()
+            .map(|rule_index| &self.rules[rule_index])
+    }
+    fn firing_rule_indices(&self, sequence: &[Symbol]) -> Vec<usize> {
+        self.rules_firing_at(sequence, sequence.len())
+    }
+    // the last order symbols of sequence
+    fn context(sequence: &[Symbol], order: usize) -> Conjunction<SymbolAtRelativeIndex<Symbol>> {
+        Conjunction(
+            (1..=order)
+                .map(|offset| SymbolAtRelativeIndex {
+                    symbol: sequence[sequence.len() - offset],
+                    relative_index: -(offset as i32),
+                })
+                .collect(),
+        )
+    }
+    // the rules with exactly this antecedent
+    fn context_rules(
+        &self,
+        context: Conjunction<SymbolAtRelativeIndex<Symbol>>,
+    ) -> impl Iterator<Item = usize> + '_ {
+        self.rules_index
+            .get(&Self::index_key(&context))
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(move |rule_index| self.rules[*rule_index].0 .0 == context)
+    }
+    // rules firing at the hole at index, given the symbols known around it
+    fn infilling_rules<'a>(
+        &'a self,
+        sequence: &'a [Option<Symbol>],
+        index: usize,
+    ) -> impl Iterator<Item = &'a (SymbolRule<Symbol>, RuleStats)> + 'a {
+        self.rules_firing_at(sequence, index)
+            .into_iter()
+            .map(|rule_index| &self.rules[rule_index])
+    }
+    // the rules that fire at index of data (data.len() being the position right after it), in
+    // rule order, looking at the symbols on both sides of it
+    fn rules_firing_at<'a, Known: Copy + Into<Option<Symbol>>>(
+        &self,
+        data: &'a [Known],
+        index: usize,
+    ) -> Vec<usize>
+    where
+        Conjunction<SymbolAtRelativeIndex<Symbol>>: Evaluate<bool, (&'a [Known], usize)>,
+    {
+        let mut candidates = self.candidate_rules_at(data, index);
+        candidates.retain(|rule_index| self.rules[*rule_index].0 .0.evaluate((data, index)));
+        candidates
+    }
+    // only the rules indexed under a known symbol around index, or without antecedent, can
+    // fire there
+    fn candidate_rules_at<Known: Copy + Into<Option<Symbol>>>(
+        &self,
+        data: &[Known],
+        index: usize,
+    ) -> Vec<usize> {
+        let mut candidates: Vec<usize> = self.rules_index.get(&None).cloned().unwrap_or_default();
+        let before = (1..self.window_size.min(index + 1)).map(|offset| -(offset as i32));
+        let after = (1..=self.lookahead.min(data.len().saturating_sub(index + 1)))
+            .map(|offset| offset as i32);
+        for relative_index in before.chain(after) {
+            if let Some(symbol) = data[(index as i32 + relative_index) as usize].into() {
+                if let Some(rule_indices) = self.rules_index.get(&Some((relative_index, symbol))) {
+                    candidates.extend(rule_indices);
+                }
+            }
+        }
+        candidates.sort_unstable();
+        candidates
+    }
+    fn index_rules(&mut self) {
+        self.rules_index.clear();
+        for (rule_index, (rule, _)) in self.rules.iter().enumerate() {
+            self.rules_index
+                .entry(Self::index_key(&rule.0))
+                .or_default()
+                .push(rule_index);
+        }
+    }
+    fn index_key(antecedent: &Conjunction<SymbolAtRelativeIndex<Symbol>>) -> Option<(i32, Symbol)> {
+        antecedent
+            .0
+            .first()
+            .map(|term| (term.relative_index, term.symbol))
+    }
+    fn derive_alphabet_from_data(data: &[Symbol]) -> Vec<Symbol> {
+        let mut seen = HashSet::new();
+        data.iter()
+            .copied()
+            .filter(|symbol| seen.insert(*symbol))
+            .collect()
     }
 }
 
-#[derive(Debug)]
-struct Rule<Term>(Term, Term);
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Rule<Antecedent, Consequent>(Antecedent, Consequent);
 
-impl<Context: Clone, Term: Evaluate<bool, Context>> Evaluate<bool, Context> for Rule<Term> {
-    fn evaluate(&self, context: Context) -> bool {
-        fn conditional(x: bool, y: bool) -> bool {
-            match (x, y) {
-                (true, true) => true,
-                (true, false) => true,
-                (false, true) => true,
-                (false, false) => true,
+#[cfg(test)]
+impl<Antecedent, Consequent> Rule<Antecedent, Consequent> {
+    fn count<Context: Clone>(&self, contexts: impl Iterator<Item = Context>) -> RuleStats
+    where
+        Antecedent: Evaluate<bool, Context>,
+        Consequent: Evaluate<bool, Context>,
+    {
+        let mut stats = RuleStats::default();
+        for context in contexts {
+            if self.0.evaluate(context.clone()) {
+                stats.antecedent_count += 1;
+                if self.1.evaluate(context) {
+                    stats.rule_count += 1;
+                }
             }
         }
-        fn biconditional(x: bool, y: bool) -> bool {
-            match (x, y) {
-                (true, true) => true,
-                (true, false) => false,
-                (false, true) => true,
-                (false, false) => true,
+        stats
+    }
+}
+
+// how many times the antecedent fired and how many of those the consequent followed,
+// with the connective between them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct RuleStats {
+    antecedent_count: u32,
+    rule_count: u32,
+    connective: Connective,
+}
+
+impl RuleStats {
+    // P(consequent | antecedent)
+    fn confidence(&self) -> f64 {
+        self.rule_count as f64 / self.antecedent_count as f64
+    }
+    // whether the consequent follows when the rule fires, with P(that | antecedent),
+    // None when the connective says nothing about it
+    fn assertion(&self) -> Option<(bool, f64)> {
+        self.connective.consequent_when_antecedent().map(|follows| {
+            if follows {
+                (true, self.confidence())
+            } else {
+                (false, 1.0 - self.confidence())
             }
-        }
-        let left = self.0.evaluate(context.clone());
-        let right = self.1.evaluate(context.clone());
-        biconditional(left, right)
+        })
+    }
+    fn asserts_consequent(&self) -> bool {
+        matches!(self.assertion(), Some((true, _)))
+    }
+    // at how many of position_count positions connective holds between the antecedent and a
+    // consequent seen consequent_count times, positions where the antecedent looks outside of
+    // the data count as not firing
+    fn holding_count(
+        &self,
+        connective: Connective,
+        consequent_count: u32,
+        position_count: u32,
+    ) -> u32 {
+        let only_antecedent = self.antecedent_count - self.rule_count;
+        let only_consequent = consequent_count.saturating_sub(self.rule_count);
+        let neither = position_count.saturating_sub(self.antecedent_count + only_consequent);
+        [
+            (true, true, self.rule_count),
+            (true, false, only_antecedent),
+            (false, true, only_consequent),
+            (false, false, neither),
+        ]
+        .into_iter()
+        .filter(|(antecedent, consequent, _)| connective.apply(*antecedent, *consequent))
+        .map(|(_, _, count)| count)
+        .sum()
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 struct SymbolAtRelativeIndex<Symbol: Copy> {
     symbol: Symbol,
     relative_index: i32,
 }
 
-impl<Symbol: Eq + Copy> Evaluate<bool, (&Vec<Symbol>, usize)> for SymbolAtRelativeIndex<Symbol> {
-    fn evaluate(&self, (data, index): (&Vec<Symbol>, usize)) -> bool {
+// unknown outside of the data and at holes
+impl<Symbol: Eq + Copy> Evaluate<TruthValue, (&[Option<Symbol>], usize)>
+    for SymbolAtRelativeIndex<Symbol>
+{
+    fn evaluate(&self, (data, index): (&[Option<Symbol>], usize)) -> TruthValue {
+        let index = index as isize + self.relative_index as isize;
+        if index < 0 || index >= data.len() as isize {
+            return TruthValue::Unknown;
+        }
+        data[index as usize].map_or(TruthValue::Unknown, |symbol| (symbol == self.symbol).into())
+    }
+}
+
+// unknown outside of the data
+impl<Symbol: Eq + Copy> Evaluate<TruthValue, (&[Symbol], usize)> for SymbolAtRelativeIndex<Symbol> {
+    fn evaluate(&self, (data, index): (&[Symbol], usize)) -> TruthValue {
         let index = index as isize + self.relative_index as isize;
         if index < 0 || index >= data.len() as isize {
-            return false;
+            return TruthValue::Unknown;
+        }
+        (data[index as usize] == self.symbol).into()
+    }
+}
+
+impl<Symbol: Eq + Copy> Evaluate<bool, (&[Symbol], usize)> for SymbolAtRelativeIndex<Symbol> {
+    fn evaluate(&self, context: (&[Symbol], usize)) -> bool {
+        Evaluate::<TruthValue, _>::evaluate(self, context) == TruthValue::True
+    }
+}
+
+#[cfg(test)]
+impl<Symbol: Copy + Eq + Hash + Debug> SequencePredictionModel<Symbol> {
+    // the original trainer: every candidate rule evaluated over the whole data
+    fn train_by_scanning(data: &[Symbol], window_size: usize) -> SequencePredictionModel<Symbol> {
+        assert!(window_size > 1);
+        // candidate rules are the ones observed at least once, in order of first occurrence
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        for index in 0..data.len() {
+            for antecedent in Self::antecedents_at(data, index, window_size, 0) {
+                let rule = Rule(
+                    antecedent,
+                    SymbolAtRelativeIndex {
+                        symbol: data[index],
+                        relative_index: 0,
+                    },
+                );
+                if seen.insert(rule.clone()) {
+                    candidates.push(rule);
+                }
+            }
+        }
+        let mut model = SequencePredictionModel {
+            window_size,
+            alphabet: Self::derive_alphabet_from_data(data),
+            corpus_hash: corpus_hash(data),
+            rules: candidates
+                .into_iter()
+                .map(|rule| {
+                    let stats = rule.count((0..data.len()).map(|index| (data, index)));
+                    (rule, stats)
+                })
+                .collect(),
+            prediction: Prediction::BestRule,
+            connective_selection: ConnectiveSelection::Fixed(Connective::Implication),
+            lookahead: 0,
+            tail: data[data.len().saturating_sub(window_size - 1)..].to_vec(),
+            rules_index: HashMap::new(),
+        };
+        model.index_rules();
+        model
+    }
+}
+
+#[test]
+fn test_single_pass_training_equals_scanning() {
+    for (text, window_size) in [
+        ("abababababab", 2),
+        ("abcabcabcabc", 3),
+        ("mamma mamma mamma mamma", 4),
+        ("nel mezzo del cammin di nostra vita", 5),
+    ] {
+        let data: Vec<char> = text.chars().collect();
+        assert_eq!(
+            SequencePredictionModel::train(&data, window_size),
+            SequencePredictionModel::train_by_scanning(&data, window_size)
+        );
+    }
+}
+
+#[test]
+fn test_rules_index_finds_every_firing_rule() {
+    let data: Vec<char> = "nel mezzo del cammin di nostra vita".chars().collect();
+    let model = SequencePredictionModel::train(&data, 4);
+    for end in 0..data.len() {
+        let sequence = &data[..end];
+        let indexed: Vec<_> = model.firing_rules(sequence).collect();
+        let scanned: Vec<_> = model
+            .rules
+            .iter()
+            .filter(|(rule, _)| rule.0.evaluate((sequence, sequence.len())))
+            .collect();
+        assert_eq!(indexed, scanned);
+    }
+}
+
+#[test]
+fn test_holding_counts_equal_scanning() {
+    let data: Vec<char> = "nel mezzo del cammin di nostra vita".chars().collect();
+    let model = SequencePredictionModel::train(&data, 3);
+    for (rule, stats) in &model.rules {
+        let consequent_count = data
+            .iter()
+            .filter(|symbol| **symbol == rule.1.symbol)
+            .count();
+        for connective in Connective::ALL {
+            let scanned = (0..data.len())
+                .filter(|index| {
+                    connective.apply(
+                        rule.0.evaluate((&data[..], *index)),
+                        rule.1.evaluate((&data[..], *index)),
+                    )
+                })
+                .count();
+            assert_eq!(
+                stats.holding_count(connective, consequent_count as u32, data.len() as u32),
+                scanned as u32
+            );
         }
-        data[index as usize] == self.symbol
     }
 }
+
+#[test]
+fn test_rules_firing_at_look_at_both_sides() {
+    let data: Vec<char> = "nel mezzo del cammin di nostra vita".chars().collect();
+    let model = SequencePredictionModel::train_bidirectional(&data, 3);
+    for index in 0..data.len() {
+        let indexed = model.rules_firing_at(&data, index);
+        let scanned: Vec<usize> = (0..model.rules.len())
+            .filter(|rule_index| model.rules[*rule_index].0 .0.evaluate((&data[..], index)))
+            .collect();
+        assert_eq!(indexed, scanned);
+    }
+}
+
+#[test]
+fn test_terms_outside_of_the_data_are_unknown() {
+    let data: Vec<char> = "abab".chars().collect();
+    let previous_is_a = Conjunction(vec![SymbolAtRelativeIndex {
+        symbol: 'a',
+        relative_index: -1,
+    }]);
+    let values: Vec<TruthValue> = (0..data.len())
+        .map(|index| previous_is_a.evaluate((&data[..], index)))
+        .collect();
+    assert_eq!(
+        values,
+        [
+            TruthValue::Unknown,
+            TruthValue::True,
+            TruthValue::False,
+            TruthValue::True
+        ]
+    );
+    // the unknown first position is not counted as a miss
+    let accuracy: Option<f64> =
+        previous_is_a.evaluate((0..data.len()).map(|index| (&data[..], index)));
+    assert_eq!(accuracy, Some(1.0 / 3.0 * 2.0));
+    let accuracy: Option<f64> = previous_is_a.evaluate([(&data[..], 0)].into_iter());
+    assert_eq!(accuracy, None);
+}
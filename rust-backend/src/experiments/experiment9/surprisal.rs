@@ -0,0 +1,34 @@
+// a run of consecutive positions whose surprisal is above a threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurprisingSpan {
+    pub start: usize,
+    // exclusive
+    pub end: usize,
+    // in bits, like the surprisals
+    pub max_surprisal: f64,
+    pub mean_surprisal: f64,
+}
+
+// the maximal runs of surprisals above threshold, in order
+pub fn surprising_spans(surprisals: &[f64], threshold: f64) -> Vec<SurprisingSpan> {
+    let mut spans: Vec<SurprisingSpan> = Vec::new();
+    let mut start: Option<usize> = None;
+    for index in 0..=surprisals.len() {
+        let surprising = index < surprisals.len() && surprisals[index] > threshold;
+        match (start, surprising) {
+            (None, true) => start = Some(index),
+            (Some(span_start), false) => {
+                let span = &surprisals[span_start..index];
+                spans.push(SurprisingSpan {
+                    start: span_start,
+                    end: index,
+                    max_surprisal: span.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    mean_surprisal: span.iter().sum::<f64>() / span.len() as f64,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    spans
+}
@@ -0,0 +1,237 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+
+pub type TokenId = u32;
+
+// every text the vocabulary does not cover is encoded as this token
+pub const UNKNOWN_TOKEN: TokenId = 0;
+pub const UNKNOWN_TOKEN_TEXT: &str = "<unk>";
+
+// a token carries the space in front of it (" acqua"), so decoding is concatenation
+// and spacing around punctuation and apostrophes survives a round trip
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tokenizer {
+    kind: TokenizerKind,
+    // token texts by id, UNKNOWN_TOKEN_TEXT first
+    vocabulary: Vec<String>,
+    #[serde(skip)]
+    ids: HashMap<String, TokenId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TokenizerKind {
+    // one token per word or punctuation mark
+    Words,
+    // words split into subwords, starting from characters (not bytes, so that accented
+    // letters stay whole) and applying the learned merges in order
+    BytePairs { merges: Vec<(String, String)> },
+}
+
+impl Tokenizer {
+    // words seen fewer than minimum_count times (with or without a space in front)
+    // become the unknown token, preceded by a space token when they were spaced
+    pub fn words(text: &str, minimum_count: usize) -> Tokenizer {
+        let words = count_words(text);
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (word, count) in &words {
+            *counts.entry(word.trim_start()).or_default() += count;
+        }
+        let vocabulary = [" ".to_string()]
+            .into_iter()
+            .chain(
+                words
+                    .iter()
+                    .filter(|(word, _)| counts[word.trim_start()] >= minimum_count)
+                    .map(|(word, _)| word.clone()),
+            )
+            .collect();
+        Self::new(TokenizerKind::Words, vocabulary)
+    }
+    pub fn byte_pairs(text: &str, merge_count: usize) -> Tokenizer {
+        let mut words: Vec<(Vec<String>, usize)> = count_words(text)
+            .into_iter()
+            .map(|(word, count)| (word.chars().map(String::from).collect(), count))
+            .collect();
+        let mut vocabulary: Vec<String> = Vec::new();
+        for (symbols, _) in &words {
+            for symbol in symbols {
+                if !vocabulary.contains(symbol) {
+                    vocabulary.push(symbol.clone());
+                }
+            }
+        }
+        let mut merges = Vec::new();
+        for _ in 0..merge_count {
+            // the most frequent adjacent pair, the first seen among equally frequent ones
+            let mut pair_counts: Vec<((String, String), usize)> = Vec::new();
+            let mut pair_indices: HashMap<(String, String), usize> = HashMap::new();
+            for (symbols, count) in &words {
+                for pair in symbols.windows(2) {
+                    let pair = (pair[0].clone(), pair[1].clone());
+                    let index = *pair_indices.entry(pair.clone()).or_insert_with(|| {
+                        pair_counts.push((pair, 0));
+                        pair_counts.len() - 1
+                    });
+                    pair_counts[index].1 += count;
+                }
+            }
+            let Some((pair, _)) = pair_counts
+                .into_iter()
+                .rev()
+                .max_by_key(|(_, count)| *count)
+            else {
+                break;
+            };
+            for (symbols, _) in words.iter_mut() {
+                merge(symbols, &pair);
+            }
+            let merged = format!("{}{}", pair.0, pair.1);
+            if !vocabulary.contains(&merged) {
+                vocabulary.push(merged);
+            }
+            merges.push(pair);
+        }
+        Self::new(TokenizerKind::BytePairs { merges }, vocabulary)
+    }
+    fn new(kind: TokenizerKind, vocabulary: Vec<String>) -> Tokenizer {
+        let vocabulary: Vec<String> = [UNKNOWN_TOKEN_TEXT.to_string()]
+            .into_iter()
+            .chain(vocabulary)
+            .collect();
+        let ids = vocabulary
+            .iter()
+            .enumerate()
+            .map(|(id, text)| (text.clone(), id as TokenId))
+            .collect();
+        Tokenizer {
+            kind,
+            vocabulary,
+            ids,
+        }
+    }
+    pub fn kind(&self) -> &TokenizerKind {
+        &self.kind
+    }
+    pub fn vocabulary(&self) -> &[String] {
+        &self.vocabulary
+    }
+    pub fn encode(&self, text: &str) -> Vec<TokenId> {
+        let mut tokens = Vec::new();
+        for word in pre_tokenize(text) {
+            match &self.kind {
+                TokenizerKind::Words => match self.ids.get(&word) {
+                    Some(id) => tokens.push(*id),
+                    None => {
+                        if word.starts_with(' ') {
+                            tokens.push(self.id(" "));
+                        }
+                        tokens.push(UNKNOWN_TOKEN);
+                    }
+                },
+                TokenizerKind::BytePairs { merges } => {
+                    let mut symbols: Vec<String> = word.chars().map(String::from).collect();
+                    for pair in merges {
+                        merge(&mut symbols, pair);
+                    }
+                    tokens.extend(symbols.iter().map(|symbol| self.id(symbol)));
+                }
+            }
+        }
+        tokens
+    }
+    pub fn decode(&self, tokens: &[TokenId]) -> String {
+        tokens.iter().map(|token| self.text(*token)).collect()
+    }
+    pub fn text(&self, token: TokenId) -> &str {
+        self.vocabulary
+            .get(token as usize)
+            .map_or(UNKNOWN_TOKEN_TEXT, String::as_str)
+    }
+    fn id(&self, text: &str) -> TokenId {
+        self.ids.get(text).copied().unwrap_or(UNKNOWN_TOKEN)
+    }
+}
+
+// the ids are derived from the vocabulary instead of being stored
+impl<'de> Deserialize<'de> for Tokenizer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct StoredTokenizer {
+            kind: TokenizerKind,
+            vocabulary: Vec<String>,
+        }
+        let stored = StoredTokenizer::deserialize(deserializer)?;
+        Ok(Self::new(
+            stored.kind,
+            stored.vocabulary.into_iter().skip(1).collect(),
+        ))
+    }
+}
+
+fn merge(symbols: &mut Vec<String>, (left, right): &(String, String)) {
+    let mut index = 0;
+    while index + 1 < symbols.len() {
+        if symbols[index] == *left && symbols[index + 1] == *right {
+            symbols[index].push_str(right);
+            symbols.remove(index + 1);
+        }
+        index += 1;
+    }
+}
+
+// words in order of first occurrence, with their counts
+fn count_words(text: &str) -> Vec<(String, usize)> {
+    let mut words: Vec<(String, usize)> = Vec::new();
+    let mut indices: HashMap<String, usize> = HashMap::new();
+    for word in pre_tokenize(text) {
+        let index = *indices.entry(word.clone()).or_insert_with(|| {
+            words.push((word, 0));
+            words.len() - 1
+        });
+        words[index].1 += 1;
+    }
+    words
+}
+
+fn is_apostrophe(character: char) -> bool {
+    character == '\'' || character == '’'
+}
+
+// words, with an apostrophe closing the word it elides ("l'acqua" is "l'" and "acqua",
+// "un po'" keeps "po'"), and single punctuation marks; any run of whitespace becomes
+// a single space in front of the following token
+pub fn pre_tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut after_space = false;
+    for character in text.chars() {
+        let word_ended = word.ends_with(is_apostrophe);
+        if character.is_whitespace() {
+            after_space = true;
+        } else if character.is_alphanumeric() && !word_ended {
+            if word.is_empty() && after_space {
+                word.push(' ');
+            }
+            word.push(character);
+            after_space = false;
+            continue;
+        } else if is_apostrophe(character) && !word.is_empty() && !word_ended {
+            word.push(character);
+            continue;
+        }
+        if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+        if character.is_alphanumeric() {
+            word.push(character);
+        } else if !character.is_whitespace() {
+            let space = if after_space { " " } else { "" };
+            tokens.push(format!("{space}{character}"));
+            after_space = false;
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
@@ -0,0 +1,85 @@
+use super::model_file::{read_model_file, write_model_file, ModelFileFormat, ModelFileHeader};
+use super::sequence_prediction_model::SequencePredictionModel;
+use super::tokenizer::{TokenId, Tokenizer};
+use serde::{Deserialize, Serialize};
+use std::io::Error;
+
+// predicts tokens of a tokenizer (whole words with Tokenizer::words) instead of characters,
+// the vocabulary is saved in the same model file
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct WordSequencePredictionModel {
+    tokenizer: Tokenizer,
+    sequence_prediction_model: SequencePredictionModel<TokenId>,
+}
+
+impl WordSequencePredictionModel {
+    pub fn train(tokenizer: Tokenizer, text: &str, window_size: usize) -> Self {
+        let data = tokenizer.encode(text);
+        WordSequencePredictionModel {
+            tokenizer,
+            sequence_prediction_model: SequencePredictionModel::train(&data, window_size),
+        }
+    }
+    pub fn train_with_backoff(tokenizer: Tokenizer, text: &str, max_order: usize) -> Self {
+        let data = tokenizer.encode(text);
+        WordSequencePredictionModel {
+            tokenizer,
+            sequence_prediction_model: SequencePredictionModel::train_with_backoff(
+                &data, max_order,
+            ),
+        }
+    }
+    // words missing from the vocabulary are counted as the unknown token
+    pub fn update(&mut self, text: &str) {
+        self.sequence_prediction_model
+            .update(&self.tokenizer.encode(text));
+    }
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+    pub fn save(&self, file_path: &str, format: ModelFileFormat) -> Result<(), Error> {
+        write_model_file(
+            file_path,
+            format,
+            self.sequence_prediction_model.header(),
+            self,
+        )
+    }
+    pub fn load(file_path: &str, format: ModelFileFormat) -> Result<Self, Error> {
+        let (header, model): (ModelFileHeader<TokenId>, Self) = read_model_file(file_path, format)?;
+        Ok(WordSequencePredictionModel {
+            sequence_prediction_model: model.sequence_prediction_model.with_header(header),
+            ..model
+        })
+    }
+    // the next length tokens, detokenized
+    pub fn predict(&self, text: &str, length: usize) -> String {
+        let mut sequence = self.tokenizer.encode(text);
+        let input_length = sequence.len();
+        self.sequence_prediction_model
+            .predict(&mut sequence, length);
+        self.tokenizer.decode(&sequence[input_length..])
+    }
+    // the next length tokens, one text each
+    pub fn predict_words(&self, text: &str, length: usize) -> Vec<String> {
+        let mut sequence = self.tokenizer.encode(text);
+        let input_length = sequence.len();
+        self.sequence_prediction_model
+            .predict(&mut sequence, length);
+        sequence[input_length..]
+            .iter()
+            .map(|token| self.tokenizer.text(*token).to_string())
+            .collect()
+    }
+    pub fn predict_distribution(
+        &self,
+        text: &str,
+        minimum_probability: Option<f64>,
+    ) -> Vec<(String, f64)> {
+        self.sequence_prediction_model
+            .predict_distribution(&self.tokenizer.encode(text), minimum_probability)
+            .into_iter()
+            .map(|(token, probability)| (self.tokenizer.text(token).to_string(), probability))
+            .collect()
+    }
+}
@@ -1,2 +1,2 @@
-mod experiment8;
+pub mod experiment8;
 pub mod experiment9;
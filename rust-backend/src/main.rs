@@ -1,5 +0,0 @@
-mod experiments;
-
-fn main() {
-    experiments::experiment9::main(); // here only to avoid "unused code" warning
-}